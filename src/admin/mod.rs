@@ -0,0 +1,176 @@
+use crate::{post::audit_result, prelude::*};
+use rocket::{
+    delete, get,
+    http::Status,
+    post,
+    response::status::Custom,
+    serde::json::{serde_json::json, Json, Value},
+    State,
+};
+use std::net::IpAddr;
+
+#[delete("/mods/<owner>/<name>")]
+pub(crate) async fn delete_mod(
+    owner: &str,
+    name: &str,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    _a: AdminGuard,
+) -> SimpleResponse {
+    let id = format!("{owner}/{name}");
+    let result =
+        found(mods.set_removed(&id, Some(timestamp())).await).map(|_| "Successfully deleted mod.");
+    audit_result(&id, "admin-delete", ip, result, mods).await
+}
+
+#[post("/mods/<owner>/<name>/restore")]
+pub(crate) async fn restore_mod(
+    owner: &str,
+    name: &str,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    _a: AdminGuard,
+) -> SimpleResponse {
+    let id = format!("{owner}/{name}");
+    let result = found(mods.set_removed(&id, None).await).map(|_| "Successfully restored mod.");
+    audit_result(&id, "admin-restore", ip, result, mods).await
+}
+
+#[post(
+    "/mods/<owner>/<name>/visibility",
+    data = "<data>",
+    format = "application/json"
+)]
+pub(crate) async fn visibility(
+    owner: &str,
+    name: &str,
+    data: Json<AdminVisibility>,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    _a: AdminGuard,
+) -> SimpleResponse {
+    let id = format!("{owner}/{name}");
+    let result = found(mods.set_unlisted(&id, data.unlisted).await).map(|_| match data.unlisted {
+        true => "Successfully unlisted mod.",
+        false => "Successfully listed mod.",
+    });
+    audit_result(&id, "admin-visibility", ip, result, mods).await
+}
+
+#[post(
+    "/mods/<owner>/<name>/secret",
+    data = "<data>",
+    format = "application/json"
+)]
+pub(crate) async fn reset_secret(
+    owner: &str,
+    name: &str,
+    data: Json<AdminSecretReset>,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    keys: &State<HookKeys>,
+    _a: AdminGuard,
+) -> SimpleResponse {
+    if let Some(e) = secret_errors(&data.new_secret) {
+        return Err(client_err(e));
+    }
+
+    let secret = crate::secrets::hash(data.new_secret.clone()).await;
+    let hook_key = keys.seal(&data.new_secret);
+
+    let id = format!("{owner}/{name}");
+    let result = found(mods.set_secret(&id, None, &secret, &hook_key).await)
+        .map(|_| "Successfully reset secret.");
+    audit_result(&id, "admin-secret", ip, result, mods).await
+}
+
+#[post(
+    "/mods/<owner>/<name>/owner",
+    data = "<data>",
+    format = "application/json"
+)]
+pub(crate) async fn transfer(
+    owner: &str,
+    name: &str,
+    data: Json<OwnershipTransfer>,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    _a: AdminGuard,
+) -> SimpleResponse {
+    if let Some(e) = owner_errors(&data.new_owner) {
+        return Err(client_err(e));
+    }
+
+    let old_id = format!("{owner}/{name}");
+    let new_id = format!("{}/{name}", data.new_owner);
+
+    let result = match mods.rename(&old_id, &new_id).await {
+        Ok(true) => Ok("Successfully transferred mod."),
+        Ok(false) => Err(Custom(Status::NotFound, "The mod doesn't exist.")),
+        Err(StoreError::Conflict) => Err(Custom(
+            Status::Conflict,
+            "The new owner already has a mod with this name.",
+        )),
+        Err(e) => {
+            dbg!(e);
+            Err(server_err(
+                "Failed to transfer mod because of an internal error.",
+            ))
+        }
+    };
+
+    // Both names get an event so the history can be followed from either
+    if result.is_ok() {
+        AuditEvent::new(&new_id, "admin-transfer", "success", ip)
+            .record(mods)
+            .await;
+    }
+    audit_result(&old_id, "admin-transfer", ip, result, mods).await
+}
+
+#[get("/mods?<page>")]
+pub(crate) async fn recent(
+    page: Option<u64>,
+    mods: &State<Mods>,
+    _a: AdminGuard,
+) -> Result<Value, Status> {
+    let query = ListQuery {
+        sort: Sort::New,
+        search: None,
+        tags: &[],
+        game: None,
+        skip: page.unwrap_or(0).saturating_mul(50),
+        limit: 50,
+        after: None,
+        hidden: true,
+        broken: true,
+    };
+
+    let entries = sc!(mods.list(&query).await, Status::InternalServerError);
+    let entries_json: Vec<Value> = entries.iter().map(expose_as_admin_json).collect();
+
+    Ok(json!(entries_json))
+}
+
+fn expose_as_admin_json(entry: &ModEntry) -> Value {
+    let mut json = crate::get::expose_as_json(entry);
+    json["published"] = json!(entry.published);
+    json["unlisted"] = json!(entry.unlisted);
+    json["deleted"] = json!(entry.deleted);
+    json["removed"] = json!(entry.removed);
+    json
+}
+
+// Turns whether a mod was found into a response
+fn found(result: Result<bool, StoreError>) -> Result<(), Custom<&'static str>> {
+    match result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Custom(Status::NotFound, "The mod doesn't exist.")),
+        Err(e) => {
+            dbg!(e);
+            Err(server_err(
+                "Failed to update mod because of an internal error.",
+            ))
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use rocket::serde::{Deserialize, Serialize};
+use semver::{Comparator, Op, Version, VersionReq};
+
+// Bits given to the minor and patch numbers when versions are packed into one integer
+const BITS: u32 = 20;
+const PART_MAX: u64 = (1 << BITS) - 1;
+const MAJOR_MAX: u64 = (1 << (62 - 2 * BITS)) - 1;
+
+/// The game versions a release supports, as packed versions from `min` up to but not including
+/// `max`. Stored next to the requirement it was computed from, so stores can filter by it.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct VersionRange {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl VersionRange {
+    pub fn contains(self, version: i64) -> bool {
+        self.min <= version && version < self.max
+    }
+}
+
+/// Packs a version into an integer that sorts the same way. Pre-release and build metadata
+/// are ignored. Fails if any part is too large to pack.
+pub(crate) fn pack(version: &Version) -> Option<i64> {
+    pack_parts(version.major, version.minor, version.patch)
+}
+
+/// Parses a game version, allowing the minor and patch numbers and a `v` prefix to be left
+/// out, so `v1.9` is `1.9.0`.
+pub(crate) fn parse_version(s: &str) -> Option<Version> {
+    let s = s.trim().trim_start_matches(['v', 'V']);
+    let padding = 2usize.saturating_sub(s.matches('.').count());
+    Version::parse(&format!("{s}{}", ".0".repeat(padding))).ok()
+}
+
+/// The versions a requirement allows. Fails if it allows none, or uses numbers too large to pack.
+pub(crate) fn range(req: &VersionReq) -> Option<VersionRange> {
+    let mut range = VersionRange {
+        min: 0,
+        max: i64::MAX,
+    };
+
+    // Every comparator must hold, so their ranges intersect
+    for comparator in &req.comparators {
+        let (min, max) = comparator_range(comparator)?;
+        range.min = range.min.max(min);
+        range.max = range.max.min(max);
+    }
+
+    match range.min < range.max {
+        true => Some(range),
+        false => None,
+    }
+}
+
+fn comparator_range(c: &Comparator) -> Option<(i64, i64)> {
+    let (major, minor, patch) = (c.major, c.minor, c.patch);
+    let lowest = pack_parts(major, minor.unwrap_or(0), patch.unwrap_or(0))?;
+
+    // The first version after the ones the comparator names, so `1.2` is followed by `1.3.0`
+    let after = match (minor, patch) {
+        (Some(minor), Some(patch)) => pack_parts(major, minor, patch + 1),
+        (Some(minor), None) => pack_parts(major, minor + 1, 0),
+        _ => pack_parts(major + 1, 0, 0),
+    }?;
+
+    let range = match c.op {
+        Op::Exact | Op::Wildcard => (lowest, after),
+        Op::Greater => (after, i64::MAX),
+        Op::GreaterEq => (lowest, i64::MAX),
+        Op::Less => (0, lowest),
+        Op::LessEq => (0, after),
+        Op::Tilde => match minor {
+            Some(minor) => (lowest, pack_parts(major, minor + 1, 0)?),
+            None => (lowest, after),
+        },
+        // Caret allows changes that don't modify the leftmost non-zero part
+        Op::Caret => match (major, minor, patch) {
+            (0, Some(0), Some(patch)) => (lowest, pack_parts(0, 0, patch + 1)?),
+            (0, Some(minor), _) => (lowest, pack_parts(0, minor + 1, 0)?),
+            _ => (lowest, pack_parts(major + 1, 0, 0)?),
+        },
+        _ => return None,
+    };
+    Some(range)
+}
+
+fn pack_parts(major: u64, minor: u64, patch: u64) -> Option<i64> {
+    if major > MAJOR_MAX || minor > PART_MAX || patch > PART_MAX {
+        return None;
+    }
+    Some(((major << (2 * BITS)) | (minor << BITS) | patch) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range_of(req: &str) -> Option<(String, String)> {
+        let range = range(&VersionReq::parse(req).unwrap())?;
+        let show = |v: i64| {
+            let v = v as u64;
+            format!(
+                "{}.{}.{}",
+                v >> (2 * BITS),
+                (v >> BITS) & PART_MAX,
+                v & PART_MAX
+            )
+        };
+        Some((show(range.min), show(range.max)))
+    }
+
+    fn pair(min: &str, max: &str) -> Option<(String, String)> {
+        Some((min.to_owned(), max.to_owned()))
+    }
+
+    #[test]
+    fn caret_edges() {
+        assert_eq!(range_of("^1.2.3"), pair("1.2.3", "2.0.0"));
+        assert_eq!(range_of("^0.2.3"), pair("0.2.3", "0.3.0"));
+        assert_eq!(range_of("^0.0.3"), pair("0.0.3", "0.0.4"));
+        assert_eq!(range_of("^0.0"), pair("0.0.0", "0.1.0"));
+        assert_eq!(range_of("^0"), pair("0.0.0", "1.0.0"));
+    }
+
+    #[test]
+    fn tilde_edges() {
+        assert_eq!(range_of("~1.2.3"), pair("1.2.3", "1.3.0"));
+        assert_eq!(range_of("~1.2"), pair("1.2.0", "1.3.0"));
+        assert_eq!(range_of("~1"), pair("1.0.0", "2.0.0"));
+    }
+
+    #[test]
+    fn empty_ranges() {
+        assert_eq!(range_of(">=1.9, <1.8"), None);
+        assert_eq!(range_of(">1.2.3, <=1.2.3"), None);
+    }
+
+    #[test]
+    fn agrees_with_semver() {
+        let reqs = [
+            "*",
+            "1",
+            "1.2",
+            "=1.2.3",
+            ">1.2",
+            ">=1.2.3",
+            "<2",
+            "<=1.2",
+            "^1.2.3",
+            "^0.2",
+            "~0.2.3",
+            ">=1.9, <2.1",
+            "1.*",
+            "1.2.*",
+        ];
+        for req in reqs {
+            let parsed = VersionReq::parse(req).unwrap();
+            let range = range(&parsed).unwrap();
+            for major in 0..4 {
+                for minor in 0..4 {
+                    for patch in 0..5 {
+                        let v = Version::new(major, minor, patch);
+                        let packed = pack(&v).unwrap();
+                        assert_eq!(range.contains(packed), parsed.matches(&v), "{req} {v}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_versions() {
+        assert_eq!(parse_version("v1.9"), Some(Version::new(1, 9, 0)));
+        assert_eq!(parse_version("2"), Some(Version::new(2, 0, 0)));
+        assert_eq!(parse_version("1.9.15"), Some(Version::new(1, 9, 15)));
+        assert_eq!(parse_version("one"), None);
+    }
+}
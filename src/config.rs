@@ -0,0 +1,202 @@
+use crate::providers::{Provider, Providers};
+use rocket::{
+    figment::{providers::Env, Figment},
+    serde::{Deserialize, Serialize},
+};
+use std::fmt;
+
+/// Server settings, read from `Rocket.toml` and `ROCKET_*` env vars under the active profile.
+/// `DB_URL`, `SECRET_KEY`, `ADMIN_TOKEN` and `ALLOW_QUERY_SECRET` are still read as they are.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct Config {
+    // `memory://`, `sqlite://<path>`, or a MongoDB URL
+    pub db_url: String,
+    // MongoDB database and collection holding mod entries
+    pub database: String,
+    pub collection: String,
+    // Encrypts the copies of secrets used to check webhook signatures
+    pub secret_key: String,
+    // Whether unsigned webhook deliveries may authenticate with `?secret=` alone
+    pub allow_query_secret: bool,
+    // Admin routes are disabled without a token
+    pub admin_token: Option<String>,
+    // How many mods `GET /mods` returns at once
+    pub page_size: u64,
+    // Binary URLs must match one of these
+    pub binary_providers: Vec<Provider>,
+    pub rate_limit: RateLimit,
+    // `http` downloads submitted binaries, `stub` pretends to for local setups
+    pub fetcher: FetchMode,
+    // In bytes
+    pub max_binary_size: u64,
+    // How often each mod's links are checked for rot, in seconds. Zero disables checking.
+    pub link_check_interval: u64,
+    // How many webhook releases can be synced in the background at once
+    pub max_syncs: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub(crate) enum FetchMode {
+    Http,
+    Stub,
+}
+
+/// Caps how many writes each IP, or each repository's webhook, can make per window. Zero requests means no cap.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct RateLimit {
+    pub requests: u32,
+    // In seconds
+    pub window: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            db_url: String::new(),
+            database: "test2".to_owned(),
+            collection: "mods".to_owned(),
+            secret_key: String::new(),
+            allow_query_secret: false,
+            admin_token: None,
+            page_size: 20,
+            binary_providers: Provider::defaults(),
+            rate_limit: RateLimit::default(),
+            fetcher: FetchMode::Http,
+            max_binary_size: 64 * 1024 * 1024,
+            link_check_interval: 24 * 60 * 60,
+            max_syncs: 16,
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            requests: 30,
+            window: 60,
+        }
+    }
+}
+
+impl Config {
+    pub fn figment() -> Figment {
+        rocket::Config::figment().merge(Env::raw().only(&[
+            "db_url",
+            "secret_key",
+            "admin_token",
+            "allow_query_secret",
+        ]))
+    }
+
+    /// Reads and validates the configuration.
+    pub fn load() -> Result<Self, String> {
+        let mut config: Config = Self::figment().extract().map_err(|e| e.to_string())?;
+
+        // An empty token is the same as no token
+        config.admin_token = config.admin_token.filter(|t| !t.is_empty());
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let db_url = &self.db_url;
+        let valid_url = db_url == "memory://"
+            || matches!(db_url.strip_prefix("sqlite://"), Some(p) if !p.is_empty())
+            || db_url.starts_with("mongodb://")
+            || db_url.starts_with("mongodb+srv://");
+
+        if db_url.is_empty() {
+            Err("`db_url` is required.".to_owned())
+        } else if !valid_url {
+            Err(format!("`db_url` must be `memory://`, `sqlite://<path>`, or a MongoDB URL, not `{db_url}`."))
+        } else if self.secret_key.is_empty() {
+            Err("`secret_key` is required.".to_owned())
+        } else if self.database.is_empty()
+            || self.database.len() > 63
+            || self
+                .database
+                .contains(['/', '\\', '.', ' ', '"', '$', '\0'])
+        {
+            Err("`database` must be 1-63 bytes without any of `/\\. \"$`.".to_owned())
+        } else if self.collection.is_empty()
+            || self.collection.len() > 120
+            || self.collection.contains(['$', '\0'])
+            || self.collection.starts_with("system.")
+        {
+            Err(
+                "`collection` must be 1-120 bytes without `$` and can't start with `system.`."
+                    .to_owned(),
+            )
+        } else if !(1..=100).contains(&self.page_size) {
+            Err("`page_size` must be 1-100.".to_owned())
+        } else if matches!(&self.admin_token, Some(t) if t.len() < 16) {
+            Err("`admin_token` must be at least 16 bytes.".to_owned())
+        } else if self.max_binary_size == 0 {
+            Err("`max_binary_size` must be positive.".to_owned())
+        } else if self.max_syncs == 0 {
+            Err("`max_syncs` must be positive.".to_owned())
+        } else if self.rate_limit.requests > 0 && self.rate_limit.window == 0 {
+            Err("`rate_limit.window` must be positive.".to_owned())
+        } else {
+            Providers::new(&self.binary_providers).map(|_| ())
+        }
+    }
+}
+
+// Prints the effective configuration without giving away secrets
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |s: &str| if s.is_empty() { "(none)" } else { "(set)" };
+        let db_url = match self.db_url.split_once('@') {
+            // MongoDB URLs can hold credentials before the host
+            Some((scheme, host)) => match scheme.split_once("://") {
+                Some((scheme, _)) => format!("{scheme}://***@{host}"),
+                None => "***".to_owned(),
+            },
+            None => self.db_url.clone(),
+        };
+
+        writeln!(f, "db_url             {db_url}")?;
+        writeln!(f, "database           {}", self.database)?;
+        writeln!(f, "collection         {}", self.collection)?;
+        writeln!(f, "secret_key         {}", redacted(&self.secret_key))?;
+        writeln!(f, "allow_query_secret {}", self.allow_query_secret)?;
+        writeln!(
+            f,
+            "admin_token        {}",
+            redacted(self.admin_token.as_deref().unwrap_or_default())
+        )?;
+        writeln!(f, "page_size          {}", self.page_size)?;
+        writeln!(f, "binary_providers")?;
+        for provider in &self.binary_providers {
+            write!(f, "    {:<14} {}", provider.name, provider.pattern)?;
+            match &provider.rewrite {
+                Some(rewrite) => writeln!(f, " -> {rewrite}")?,
+                None => writeln!(f)?,
+            }
+        }
+        let fetcher = match self.fetcher {
+            FetchMode::Http => "http",
+            FetchMode::Stub => "stub",
+        };
+        writeln!(f, "fetcher            {fetcher}")?;
+        writeln!(f, "max_binary_size    {} bytes", self.max_binary_size)?;
+        match self.link_check_interval {
+            0 => writeln!(f, "link_check_interval never")?,
+            n => writeln!(f, "link_check_interval {n}s")?,
+        }
+        writeln!(f, "max_syncs          {}", self.max_syncs)?;
+        match self.rate_limit.requests {
+            0 => writeln!(f, "rate_limit         none"),
+            n => writeln!(
+                f,
+                "rate_limit         {n} writes per {}s",
+                self.rate_limit.window
+            ),
+        }
+    }
+}
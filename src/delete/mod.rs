@@ -0,0 +1 @@
+pub(crate) mod one_mod;
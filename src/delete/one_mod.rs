@@ -0,0 +1,30 @@
+use crate::post::{audit_result, authorize};
+use crate::prelude::*;
+use rocket::{delete, serde::json::Json, State};
+use std::net::IpAddr;
+
+#[delete("/<owner>/<name>", data = "<data>", format = "application/json")]
+pub(crate) async fn delete_mod(
+    owner: &str,
+    name: &str,
+    data: Json<Credentials>,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    _t: Throttle,
+) -> SimpleResponse {
+    let id = format!("{owner}/{name}");
+    let entry = authorize(&id, data.0.secret, "delete", ip, mods).await?;
+
+    // The entry stays in the database so nobody else can claim its name
+    let result = match mods.set_deleted(&entry.id, Some(timestamp())).await {
+        Ok(_) => Ok("Successfully deleted mod."),
+        Err(e) => {
+            dbg!(e);
+            Err(server_err(
+                "Failed to delete mod because of an internal error.",
+            ))
+        }
+    };
+
+    audit_result(&id, "delete", ip, result, mods).await
+}
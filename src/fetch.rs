@@ -0,0 +1,325 @@
+use crate::config::FetchMode;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    StatusCode, Url,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Downloads files from third-party hosts on the server's behalf.
+#[rocket::async_trait]
+pub(crate) trait Fetcher: Send + Sync {
+    /// Downloads the file at `url`, failing if it's larger than `limit` bytes.
+    async fn fetch(&self, url: &str, limit: u64) -> Result<Vec<u8>, FetchError>;
+
+    /// Checks that the file at `url` can still be downloaded, without downloading it.
+    async fn check(&self, url: &str) -> Result<(), FetchError>;
+}
+
+pub(crate) fn client(mode: FetchMode) -> Arc<dyn Fetcher> {
+    match mode {
+        FetchMode::Http => Arc::new(HttpFetcher::new()),
+        FetchMode::Stub => Arc::new(StubFetcher),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum FetchError {
+    // The host couldn't be reached or didn't respond with the file
+    Unreachable,
+    TooLarge,
+}
+
+/// Fetches over HTTP(S).
+pub(crate) struct HttpFetcher(reqwest::Client);
+
+impl HttpFetcher {
+    pub fn new() -> Self {
+        // Redirects are checked like the URLs submitters give
+        let redirects = Policy::custom(|attempt| {
+            if attempt.previous().len() >= 10 {
+                attempt.error("too many redirects")
+            } else if is_allowed(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.error("redirected to a private address")
+            }
+        });
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent(concat!("rdb/", env!("CARGO_PKG_VERSION")))
+            .redirect(redirects)
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()
+            .expect("failed to build HTTP client");
+
+        Self(client)
+    }
+}
+
+/// Resolves hosts to their public addresses only, so submitted URLs can't reach the server's
+/// own network. Hosts with no public addresses fail to resolve.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+
+        Box::pin(async move {
+            let addrs = rocket::tokio::net::lookup_host((host, 0)).await?;
+            let public: Vec<SocketAddr> = addrs.filter(|a| is_public(a.ip())).collect();
+
+            if public.is_empty() {
+                return Err("the host has no public addresses".into());
+            }
+            Ok(Box::new(public.into_iter()) as Addrs)
+        })
+    }
+}
+
+// URLs with an IP address for a host skip the resolver, so they're checked before requesting them
+fn parse_allowed(url: &str) -> Result<Url, FetchError> {
+    match Url::parse(url) {
+        Ok(url) if is_allowed(&url) => Ok(url),
+        _ => Err(FetchError::Unreachable),
+    }
+}
+
+fn is_allowed(url: &Url) -> bool {
+    let public = match url.host() {
+        Some(url::Host::Domain(_)) => true,
+        Some(url::Host::Ipv4(ip)) => is_public(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => is_public(IpAddr::V6(ip)),
+        None => false,
+    };
+    public && matches!(url.scheme(), "https" | "http")
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            // IPv4-mapped and NAT64 addresses lead to IPv4 hosts
+            let [a, b, c, d, e, f, g, h] = ip.segments();
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_v4(v4);
+            }
+            if [a, b, c, d, e, f] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [g1, g2] = g.to_be_bytes();
+                let [h1, h2] = h.to_be_bytes();
+                return is_public_v4(Ipv4Addr::new(g1, g2, h1, h2));
+            }
+
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                || (a == 0x2001 && b == 0xdb8))
+        }
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || a == 0
+        || a >= 240
+        // Shared address space used by carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b)))
+}
+
+#[rocket::async_trait]
+impl Fetcher for HttpFetcher {
+    async fn fetch(&self, url: &str, limit: u64) -> Result<Vec<u8>, FetchError> {
+        let mut response = self
+            .0
+            .get(parse_allowed(url)?)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|_| FetchError::Unreachable)?;
+
+        if matches!(response.content_length(), Some(len) if len > limit) {
+            return Err(FetchError::TooLarge);
+        }
+
+        // Hosts can lie about the length or leave it out, so the limit is checked as it streams
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|_| FetchError::Unreachable)?
+        {
+            if body.len() as u64 + chunk.len() as u64 > limit {
+                return Err(FetchError::TooLarge);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    async fn check(&self, url: &str) -> Result<(), FetchError> {
+        let url = parse_allowed(url)?;
+        let response = self
+            .0
+            .head(url.clone())
+            .send()
+            .await
+            .map_err(|_| FetchError::Unreachable)?;
+
+        // Some hosts only answer GET, whose body can be left unread
+        let response = match response.status() {
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => self
+                .0
+                .get(url)
+                .send()
+                .await
+                .map_err(|_| FetchError::Unreachable)?,
+            _ => response,
+        };
+
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(FetchError::Unreachable),
+        }
+    }
+}
+
+/// Never touches the network. PNG files are blank icons, manifests don't exist, and every other
+/// file's contents are its own URL, so local setups get distinct, predictable hashes.
+pub(crate) struct StubFetcher;
+
+#[rocket::async_trait]
+impl Fetcher for StubFetcher {
+    async fn fetch(&self, url: &str, limit: u64) -> Result<Vec<u8>, FetchError> {
+        let body = if url.ends_with(".png") {
+            crate::icons::blank()
+        } else if url.ends_with("/rdb.json") || url.ends_with("/rdb.toml") {
+            return Err(FetchError::Unreachable);
+        } else {
+            url.as_bytes().to_vec()
+        };
+
+        if body.len() as u64 > limit {
+            Err(FetchError::TooLarge)
+        } else {
+            Ok(body)
+        }
+    }
+
+    async fn check(&self, _url: &str) -> Result<(), FetchError> {
+        Ok(())
+    }
+}
+
+/// Fetches the files that submissions link to.
+#[derive(Clone)]
+pub(crate) struct Fetch {
+    client: Arc<dyn Fetcher>,
+    max_binary_size: u64,
+}
+
+impl Fetch {
+    pub fn new(client: Arc<dyn Fetcher>, max_binary_size: u64) -> Self {
+        Self {
+            client,
+            max_binary_size,
+        }
+    }
+
+    /// Downloads a binary and returns its hex SHA-256 and size.
+    pub async fn binary(&self, url: &str) -> Result<(String, u64), &'static str> {
+        match self.hash(url).await {
+            Ok(hash) => Ok(hash),
+            Err(FetchError::Unreachable) => Err("The binary couldn't be downloaded."),
+            Err(FetchError::TooLarge) => Err("The binary is too large."),
+        }
+    }
+
+    /// Downloads one of a release's other files and returns its hex SHA-256 and size.
+    pub async fn file(&self, url: &str) -> Result<(String, u64), &'static str> {
+        match self.hash(url).await {
+            Ok(hash) => Ok(hash),
+            Err(FetchError::Unreachable) => Err("One of the files couldn't be downloaded."),
+            Err(FetchError::TooLarge) => Err("One of the files is too large."),
+        }
+    }
+
+    async fn hash(&self, url: &str) -> Result<(String, u64), FetchError> {
+        let body = self.client.fetch(url, self.max_binary_size).await?;
+        Ok((hex::encode(Sha256::digest(&body)), body.len() as u64))
+    }
+
+    /// Downloads a mod manifest, if there is one. Hosts that can't be reached are treated as
+    /// having none, since manifests are optional.
+    pub async fn manifest(&self, url: &str) -> Result<Option<Vec<u8>>, &'static str> {
+        const MAX_MANIFEST_SIZE: u64 = 64 * 1024;
+
+        match self.client.fetch(url, MAX_MANIFEST_SIZE).await {
+            Ok(body) => Ok(Some(body)),
+            Err(FetchError::Unreachable) => Ok(None),
+            Err(FetchError::TooLarge) => Err("The manifest is too large."),
+        }
+    }
+
+    /// Downloads an icon and returns a normalised copy of it.
+    pub async fn icon(&self, url: &str) -> Result<Vec<u8>, &'static str> {
+        const MAX_ICON_SIZE: u64 = 1024 * 1024;
+
+        match self.client.fetch(url, MAX_ICON_SIZE).await {
+            Ok(body) => crate::icons::normalise(&body),
+            Err(FetchError::Unreachable) => Err("The icon couldn't be downloaded."),
+            Err(FetchError::TooLarge) => Err("The icon is too large."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(url: &str) -> bool {
+        parse_allowed(url).is_ok()
+    }
+
+    #[test]
+    fn rejects_private_hosts() {
+        assert!(allowed(
+            "https://raw.githubusercontent.com/a/b/1.0.0/icon.png"
+        ));
+        assert!(allowed("https://1.1.1.1/icon.png"));
+
+        assert!(!allowed("https://127.0.0.1/icon.png"));
+        assert!(!allowed("http://10.0.0.5:8080/admin"));
+        assert!(!allowed("http://192.168.1.1/"));
+        assert!(!allowed("http://169.254.169.254/latest/meta-data/"));
+        assert!(!allowed("http://100.64.0.1/"));
+        assert!(!allowed("http://0.0.0.0/"));
+        assert!(!allowed("http://[::1]/"));
+        assert!(!allowed("http://[fd00::1]/"));
+        assert!(!allowed("http://[fe80::1]/"));
+        assert!(!allowed("http://[::ffff:127.0.0.1]/"));
+        assert!(!allowed("http://[64:ff9b::a00:1]/"));
+        assert!(!allowed("file:///etc/passwd"));
+    }
+
+    #[rocket::async_test]
+    async fn resolves_public_addresses_only() {
+        let resolve = |host: &str| PublicResolver.resolve(host.parse().unwrap());
+        assert!(resolve("localhost").await.is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{get::ApiResponse, prelude::*};
+use rocket::{http::Status, serde::json::serde_json::json, State};
+
+#[rocket::get("/<owner>/<name>/audit?<page>")]
+pub(crate) async fn audit(
+    owner: &str,
+    name: &str,
+    page: Option<u64>,
+    mods: &State<Mods>,
+    _a: AdminGuard,
+) -> ApiResponse {
+    let id = format!("{owner}/{name}");
+    let skip = page.unwrap_or(0).saturating_mul(50);
+    let events = sc!(
+        mods.audit_log(&id, skip, 50).await,
+        Status::InternalServerError
+    );
+
+    Ok(json!(events))
+}
@@ -0,0 +1,24 @@
+use crate::prelude::*;
+use rocket::{http::Status, response::Redirect, State};
+use std::net::IpAddr;
+
+#[rocket::get("/<owner>/<name>/download")]
+pub(crate) async fn download(
+    owner: &str,
+    name: &str,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+) -> Result<Redirect, Status> {
+    let entry = super::find_entry(owner, name, mods).await?;
+
+    // Each IP address counts once per day
+    let day = timestamp() / (24 * 60 * 60);
+    let dedup = ip.map(|ip| format!("{day} {ip}"));
+
+    // A failure to count a download shouldn't prevent the download
+    if let Err(e) = mods.count_download(&entry.id, dedup).await {
+        println!("Error while counting download of {}: {e}", &entry.id);
+    }
+
+    Ok(Redirect::found(entry.info.binary))
+}
@@ -0,0 +1,24 @@
+use crate::prelude::*;
+use rocket::{
+    http::{ContentType, Status},
+    response::Redirect,
+    Responder, State,
+};
+
+#[derive(Responder)]
+pub(crate) enum Icon {
+    Mirrored(Vec<u8>, ContentType),
+    // Mods from before icons were mirrored only have a URL
+    Original(Redirect),
+}
+
+#[rocket::get("/<owner>/<name>/icon.png")]
+pub(crate) async fn icon(owner: &str, name: &str, mods: &State<Mods>) -> Result<Icon, Status> {
+    let entry = super::find_entry(owner, name, mods).await?;
+
+    match sc!(mods.icon(&entry.id).await, Status::InternalServerError) {
+        Some(png) => Ok(Icon::Mirrored(png, ContentType::PNG)),
+        None if !entry.info.icon.is_empty() => Ok(Icon::Original(Redirect::found(entry.info.icon))),
+        None => Err(Status::NotFound),
+    }
+}
@@ -6,7 +6,15 @@ pub(crate) async fn index() -> &'static str {
 Gets this page.
 
 GET /mods/count
-Gets the number of mods in the database.
+Gets the number of listed mods in the database.
+
+GET /tags
+Gets every tag a mod can have, and how many listed mods have it. Example response body:
+[
+    { "tag": "accessibility", "count": 0 },
+    { "tag": "arena", "count": 4 },
+    ...
+]
 
 GET /mods/<owner>/<name>
 Gets a specific mod. Example response body:
@@ -18,21 +26,141 @@ Gets a specific mod. Example response body:
     "description": "A plugin for Rain World",
     "homepage": "",
     "version": "0.3.0",
-    "icon": "https://raw.githubusercontent.com/Dual-Iron/centipede-shields/master/wallpounce_icon.png",
-    "binary": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll"
+    "icon": "https://rdb.dual-iron.xyz/mods/Dual-Iron/centipede-shields/icon.png",
+    "icon_source": "https://raw.githubusercontent.com/Dual-Iron/centipede-shields/master/wallpounce_icon.png",
+    "binary": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll",
+    "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+    "size": 24576,
+    "files": [
+        {
+            "name": "CentiShields.dll",
+            "url": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll",
+            "role": "plugin",
+            "path": "BepInEx/plugins/CentiShields.dll",
+            "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+            "size": 24576
+        }
+    ],
+    "dependencies": {},
+    "tags": ["creature", "gameplay"],
+    "game_versions": ">=1.9",
+    "loader_version": "^5.4",
+    "last_checked": 1641948031,
+    "broken": false,
+    "withdrawn": false,
+    "prerelease": null
+}
+`icon` is rdb's copy of the icon, served by `GET /mods/<owner>/<name>/icon.png`. `icon_source` is the URL it was copied from.
+`last_checked` is when the icon and file links were last checked, or null if they haven't been since the latest release.
+`broken` is whether any link was dead at the time.
+`withdrawn` is whether the current release was unpublished or deleted on GitHub since it was submitted. Clients shouldn't install withdrawn releases.
+`prerelease` is the newest prerelease from the GitHub webhook, in the same format as `GET /mods/<owner>/<name>/versions`, or null if there's none newer than `version`.
+`files` is every file the release installs, including `binary`. `path` is where it goes, relative to the game's folder.
+`role` is `plugin`, `patch` or `asset`. Releases from before files were recorded list just their binary.
+
+GET /mods/<owner>/<name>/versions
+Gets every release of a specific mod, newest first. Example response body:
+[
+    {
+        "published": 1641861631,
+        "description": "A plugin for Rain World",
+        "homepage": "",
+        "version": "0.3.0",
+        "icon": "https://raw.githubusercontent.com/Dual-Iron/centipede-shields/master/wallpounce_icon.png",
+        "binary": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll",
+        "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        "size": 24576,
+        "files": [
+            {
+                "name": "CentiShields.dll",
+                "url": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll",
+                "role": "plugin",
+                "path": "BepInEx/plugins/CentiShields.dll",
+                "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+                "size": 24576
+            }
+        ],
+        "dependencies": {},
+        "tags": ["creature", "gameplay"],
+        "game_versions": ">=1.9",
+        "loader_version": "^5.4"
+    }
+]
+
+GET /mods/<owner>/<name>/versions/<version>
+Gets a specific release of a specific mod. The response body is one element of the array above.
+
+GET /mods/<owner>/<name>/download
+Redirects to a specific mod's binary and counts the download.
+Each IP address counts as at most one download per mod per day.
+
+GET /mods/<owner>/<name>/icon.png
+Gets a copy of a specific mod's icon, taken when its latest release was submitted.
+Mods submitted before icons were copied redirect to their icon's URL instead.
+
+GET /mods/<owner>/<name>/resolve?<version>
+Gets every mod that must be installed alongside a specific mod, including the mod itself.
+- `version` picks a release of the mod to resolve; the latest release is used if omitted
+Each dependency uses the newest release that satisfies every requirement on it. Example response body:
+[
+    {
+        "name": "centipede-shields",
+        "owner": "Dual-Iron",
+        "version": "0.3.0",
+        "binary": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll",
+        "files": [ ...files in the same format as `GET /mods/<owner>/<name>`... ]
+    }
+]
+If no release satisfies every requirement, responds with 409 Conflict. Example response body:
+{
+    "error": "conflict",
+    "mod": "Dual-Iron/some-library",
+    "requirements": [{ "required_by": "Dual-Iron/centipede-shields", "requirement": "^2.0" }],
+    "available": ["1.0.0", "1.1.0"]
 }
 
-GET /mods?<page>&<sort>&<search>
-Gets a page of mods. Each page is an array with 20 or fewer elements.
+GET /mods?<page>&<sort>&<search>&<tags>&<game>&<broken>
+Gets a page of mods. Each page is an array with 20 or fewer elements, unless the server is configured otherwise.
 - `page` describes how many pages to skip
-- `sort` can be one of `new`, `old`, `most-downloads`, or `least-downloads`
-- `search` filters by mods whose names match the query parameter
+- `sort` can be one of `new`, `old`, `most-downloads`, `least-downloads`, `relevance`, `name`, `published-new`, `published-old`, or `trending`
+  - `new` and `old` order by when the mod was last updated, and `published-new` and `published-old` by when it was first published
+  - `relevance` puts the best matches for `search` first, and acts like `new` without a search
+  - `name` orders by owner, then name, alphabetically
+  - `trending` puts the most downloads over the last week first
+- `search` filters by mods whose name, owner, tags or description match the query parameter
+  - words match in part, so `centi` finds `centipede-shields`, and camelCase is split, so `shields` finds `CentiShields`
+  - small typos are tolerated, so `sheilds` finds `centipede-shields`
+  - names count for more than owners and tags, and those count for more than descriptions
+- `tags` is a comma-separated list of tags, like `creature,cosmetic`, and filters by mods with every one of them
+- `game` is a game version, like `1.9.15`, and filters by mods whose `game_versions` allow it. Mods without `game_versions` are left out.
+- `broken=false` leaves out mods whose binary or icon couldn't be downloaded when last checked
+
+GET /mods/list?<sort>&<search>&<tags>&<game>&<broken>&<per_page>&<cursor>
+Gets a page of mods, along with where the next page starts. Takes the same `sort`, `search`, `tags`, `game` and `broken` parameters as `GET /mods`.
+Unlike `page`, cursors don't skip or repeat mods when mods are added while paging through. Example response body:
+{
+    "items": [ ...mods in the same format as `GET /mods`... ],
+    "next_cursor": "6e65773a313634313836313633313a4475616c2d49726f6e2f63656e7469706564652d736869656c6473",
+    "total": 57
+}
+- `per_page` is how many mods to return, from 1 to 100; defaults to the same size as `GET /mods` pages
+- `cursor` is the `next_cursor` of the previous page; leave it out to get the first page
+- `next_cursor` is null on the last page, and only works with the `sort` it was made with
+- `total` is how many mods match `search`, `tags`, `game` and `broken` across every page
+
+GET /mods/suggest?<q>
+Gets up to 10 listed mods that start with what's been typed so far, most downloaded first. Cheap enough to call on every keystroke.
+Each word in `q` must start a word of the mod's owner or name. Words shorter than 3 letters are ignored. Example response body:
+["Dual-Iron/centipede-shields", "Dual-Iron/centipede-lib"]
 
 POST /mods Content-Type=application/json
 Submits a mod to the database.
 If a mod with the same name+owner already exists, the `secret` key must match as well.
-The icon should be a 128x128 PNG file.
-Every binary must be either a GitHub release asset, Google Drive file, or a Discord attachment.
+The icon must be a 128x128 PNG file. Submissions are rejected if it can't be downloaded.
+Every binary must be a GitHub, GitLab or Codeberg release asset, a Google Drive file, or a Discord attachment, unless the server is configured otherwise.
+The binary is downloaded once on submission, and its SHA-256 and size in bytes are recorded as `sha256` and `size`. Clients should check downloads against them.
+Submissions are rejected if the binary can't be downloaded or is too large. Releases from before this have null `sha256` and `size`.
+rdb never downloads from private or local network addresses, including through redirects, so links to them can't be downloaded.
 Example request body:
 {
     "name": "centipede-shields",
@@ -42,22 +170,105 @@ Example request body:
     "homepage": "",
     "version": "0.3.0",
     "icon": "https://raw.githubusercontent.com/Dual-Iron/centipede-shields/master/wallpounce_icon.png",
-    "binary": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll"
+    "binary": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll",
+    "dependencies": {
+        "Dual-Iron/some-library": "^1.0"
+    },
+    "tags": ["creature", "gameplay"],
+    "game_versions": ">=1.9",
+    "loader_version": "^5.4",
+    "files": [
+        { "name": "CentiShields.dll", "url": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/CentiShields.dll" },
+        { "name": "shields.png", "url": "https://github.com/Dual-Iron/centipede-shields/releases/download/0.3.0/shields.png", "role": "asset" }
+    ]
 }
+`dependencies` is optional. Each key is an existing mod's `owner/name` and each value is a semver version requirement.
+`tags` is optional. Mods can have up to 5 tags, each from `GET /tags`.
+`game_versions` and `loader_version` are optional semver version requirements on the game and mod loader versions the release works with.
+`files` is optional, and lists up to 20 files the release installs. If present, one of them must be the binary. Each file is downloaded and hashed like the binary.
+- `role` is `plugin`, `patch` or `asset`; defaults to `plugin` for DLLs and `asset` otherwise
+- `path` is where the file goes, relative to the game's folder; defaults to `BepInEx/patchers/<name>` for patches and `BepInEx/plugins/<name>` otherwise
 
-POST /github?<secret> X-GitHub-Event=ping
+POST /mods/<owner>/<name>/secret Content-Type=application/json
+Changes a mod's secret. The new secret must follow the same rules as a submission's secret.
+Remember to change the secret of the mod's GitHub webhook too, if it has one.
+Example request body:
+{
+    "secret": "not telling you this",
+    "new_secret": "not telling you this either"
+}
+
+POST /mods/<owner>/<name>/visibility Content-Type=application/json
+Lists or unlists a mod. Unlisted mods are left out of `GET /mods` and `GET /mods/count`, but can still be fetched directly.
+Example request body:
+{
+    "secret": "not telling you this",
+    "unlisted": true
+}
+
+DELETE /mods/<owner>/<name> Content-Type=application/json
+Deletes a mod. Deleted mods can't be fetched, but their name stays reserved.
+Submitting a new release with the same secret restores the mod.
+Mods taken down by a moderator can only be restored by a moderator, and new releases of them are rejected.
+Example request body:
+{
+    "secret": "not telling you this"
+}
+
+POST and DELETE requests to /mods and deliveries to /github are rate limited per IP. Authenticated deliveries are also limited per repository. Requests over the limit get 429 Too Many Requests.
+If too many releases are already being synced, deliveries get 503 Service Unavailable and can be redelivered later.
+
+/admin/...  Authorization=Bearer <token>
+Moderation routes. They require the server's admin token and are disabled if it has none.
+- DELETE /admin/mods/<owner>/<name> takes a mod down. Unlike an owner deleting it, new releases can't restore it
+- POST /admin/mods/<owner>/<name>/restore restores a deleted mod, including one taken down with DELETE /admin/mods/<owner>/<name>
+- POST /admin/mods/<owner>/<name>/visibility with { "unlisted": bool } lists or unlists a mod
+- POST /admin/mods/<owner>/<name>/secret with { "new_secret": string } resets a mod's secret
+- POST /admin/mods/<owner>/<name>/owner with { "new_owner": string } transfers a mod to a new owner
+- GET /admin/mods?<page> gets the 50 most recently updated mods, including `published`, `unlisted`, `deleted` and `removed` fields
+- GET /mods/<owner>/<name>/audit?<page> gets the 50 most recent changes and attempted changes to a mod
+
+POST /github X-GitHub-Event=ping
 Verifies a GitHub webhook for submitting rdb mods automatically.
 
-POST /github?<secret> X-GitHub-Event=release
-Submits a mod to the database.
-If a mod with the same name+owner already exists, the `secret` key must match as well.
-The icon should be a 128x128 PNG file.
+POST /github X-GitHub-Event=release
+Submits a mod to the database. What happens depends on the delivery's action:
+- `released` and `edited` submit the release, or store it as the mod's `prerelease` if it's a prerelease. Editing the mod's current release or prerelease is ignored.
+- `prereleased` stores the release as the mod's `prerelease`. A mod's first release can't be a prerelease.
+- `unpublished` and `deleted` mark the release `withdrawn` if it's the mod's current release, or remove it if it's the mod's prerelease
+- anything else is ignored, as are drafts. `published` is ignored because `released` or `prereleased` is always sent with it.
+Ignored deliveries respond with 200 OK and say why they were ignored.
+Deliveries are checked before responding, and rejected releases get the same errors as `POST /mods`. A release's files are checked after responding, since downloading every file can take longer than GitHub waits, so it shows up on rdb once they are.
+The icon must be a 128x128 PNG file. Submissions are rejected if it can't be downloaded.
+Set the webhook's secret to your mod's secret. Deliveries without a valid `X-Hub-Signature-256` header are rejected.
+Only mods that are already in rdb can be updated this way, so submit a mod's first release to `POST /mods`.
+Don't put the secret in the webhook's URL. `?secret=` is rejected unless the server allows unsigned deliveries to use it.
 This endpoint should be used by GitHub webhooks. Setup: https://user-images.githubusercontent.com/31146412/163689916-df787775-ce33-478e-b7a1-0edac45585dd.mp4
 
+Fields come from the release as shown below, unless the release has a manifest that says otherwise.
+A manifest is an `rdb.json` or `rdb.toml` file. rdb looks for one, in order:
+- as a release asset, which is never used as the binary
+- as a code block in the release notes whose language is `rdb.json` or `rdb.toml`
+- at the top of the repository at the release's tag
+Every field is optional. Example `rdb.toml`:
+    binary = "CentiShields.dll"         # the name of a release asset
+    icon = "assets/icon.png"            # a path in the repository, or a URL
+    description = "A plugin for Rain World"
+    tags = ["creature", "gameplay"]
+    game_versions = ">=1.9"
+    loader_version = "^5.4"
+    [dependencies]
+    "Dual-Iron/some-library" = "^1.0"
+    [files]                             # the assets to install; every asset if left out
+    "CentiShields.dll" = {}
+    "CentiPatch.dll" = { role = "patch" }
+    "shields.png" = { path = "BepInEx/plugins/centi/shields.png" }
+Releases with a manifest that can't be read or has unknown fields are rejected.
+
 ╔═════════════╦═══════════════════════════════════════════════════════╗
 ║    FIELD    ║                  WHERE IT COMES FROM                  ║
 ╠═════════════╬═══════════════════════════════════════════════════════╣
-║ secret      ║ Given in the URL query parameter.                     ║
+║ secret      ║ The webhook's secret.                                 ║
 ╠═════════════╬═══════════════════════════════════════════════════════╣
 ║ name        ║ The repository's name.                                ║
 ╠═════════════╬═══════════════════════════════════════════════════════╣
@@ -71,6 +282,8 @@ This endpoint should be used by GitHub webhooks. Setup: https://user-images.gith
 ╠═════════════╬═══════════════════════════════════════════════════════╣
 ║ binary      ║ The release's last asset when sorted alphabetically.  ║
 ╠═════════════╬═══════════════════════════════════════════════════════╣
+║ files       ║ Every release asset.                                  ║
+╠═════════════╬═══════════════════════════════════════════════════════╣
 ║ homepage    ║ The repository's homepage or readme URL.              ║
 ╚═════════════╩═══════════════════════════════════════════════════════╝
 "#
@@ -0,0 +1,124 @@
+use super::{
+    many_mods::{get_game, get_sort, get_tags},
+    ApiResponse,
+};
+use crate::{prelude::*, store::Cursor};
+use rocket::{
+    http::Status,
+    serde::json::{serde_json::json, Value},
+    State,
+};
+
+const MAX_PER_PAGE: u64 = 100;
+
+#[rocket::get("/list?<sort>&<search>&<tags>&<game>&<broken>&<per_page>&<cursor>")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn list_mods(
+    sort: Option<&str>,
+    search: Option<&str>,
+    tags: Option<&str>,
+    game: Option<&str>,
+    broken: Option<bool>,
+    per_page: Option<u64>,
+    cursor: Option<&str>,
+    mods: &State<Mods>,
+    config: &State<Config>,
+) -> ApiResponse {
+    let sort_name = sort.unwrap_or("new");
+    let sort = get_sort(sort_name, search).ok_or(Status::BadRequest)?;
+    let tags = get_tags(tags).ok_or(Status::BadRequest)?;
+    let game = get_game(game).ok_or(Status::BadRequest)?;
+
+    let per_page = per_page.unwrap_or(config.page_size);
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(Status::BadRequest);
+    }
+
+    let after = match cursor {
+        Some(c) => Some(decode_cursor(c, sort_name).ok_or(Status::BadRequest)?),
+        None => None,
+    };
+
+    // Relevance can't be resumed from an entry, so its cursors hold an offset instead
+    let (skip, after) = match (sort.keyset(), after) {
+        (false, Some(c)) => (c.key.max(0) as u64, None),
+        (_, after) => (0, after),
+    };
+
+    // One extra entry tells whether there's another page
+    let query = ListQuery {
+        sort,
+        search,
+        tags: &tags,
+        game,
+        skip,
+        limit: per_page + 1,
+        after,
+        hidden: false,
+        broken: broken.unwrap_or(true),
+    };
+
+    let mut entries = sc!(mods.list(&query).await, Status::InternalServerError);
+    let total = sc!(
+        mods.count_matching(&query).await,
+        Status::InternalServerError
+    );
+
+    let next_cursor = match entries.len() as u64 > per_page {
+        true => {
+            entries.truncate(per_page as usize);
+            entries.last().map(|e| match sort.keyset() {
+                true => encode_cursor(sort_name, sort.key(e), &e.id),
+                false => encode_cursor(sort_name, (skip + per_page) as i64, ""),
+            })
+        }
+        false => None,
+    };
+    let items: Vec<Value> = entries.iter().map(super::expose_as_json).collect();
+
+    Ok(json!({
+        "items": items,
+        "next_cursor": next_cursor,
+        "total": total
+    }))
+}
+
+// Cursors are opaque to clients, and only valid for the sort they were made with
+fn encode_cursor(sort: &str, key: i64, id: &str) -> String {
+    hex::encode(format!("{sort}:{key}:{id}"))
+}
+
+fn decode_cursor(cursor: &str, sort: &str) -> Option<Cursor> {
+    let cursor = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+    let mut parts = cursor.splitn(3, ':');
+
+    if parts.next()? != sort {
+        return None;
+    }
+    let key = parts.next()?.parse().ok()?;
+    let id = parts.next()?.to_owned();
+
+    Some(Cursor { key, id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = decode_cursor(&encode_cursor("new", 1641861631, "a/b:c"), "new").unwrap();
+        assert_eq!(cursor.key, 1641861631);
+        assert_eq!(cursor.id, "a/b:c");
+
+        let negative = decode_cursor(&encode_cursor("old", -5, "a/b"), "old").unwrap();
+        assert_eq!(negative.key, -5);
+    }
+
+    #[test]
+    fn cursors_only_work_with_their_sort() {
+        assert!(decode_cursor(&encode_cursor("new", 1, "a/b"), "old").is_none());
+        assert!(decode_cursor("not hex", "new").is_none());
+        assert!(decode_cursor(&hex::encode("new:one:a/b"), "new").is_none());
+    }
+}
@@ -1,55 +1,69 @@
 use crate::{get::ApiResponse, prelude::*};
-use mongodb::{bson::doc, options::FindOptions};
 use rocket::{
     http::Status,
     serde::json::{serde_json::json, Value},
 };
 
-#[rocket::get("/?<page>&<sort>&<search>")]
+#[rocket::get("/?<page>&<sort>&<search>&<tags>&<game>&<broken>")]
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn many_mods(
     page: Option<u64>,
     sort: Option<&str>,
     search: Option<&str>,
+    tags: Option<&str>,
+    game: Option<&str>,
+    broken: Option<bool>,
     mods: &rocket::State<Mods>,
+    config: &rocket::State<Config>,
 ) -> ApiResponse {
-    use rocket::futures::TryStreamExt;
+    let tags = get_tags(tags).ok_or(Status::BadRequest)?;
+    let query = ListQuery {
+        sort: get_sort(sort.unwrap_or("new"), search).ok_or(Status::BadRequest)?,
+        search,
+        tags: &tags,
+        game: get_game(game).ok_or(Status::BadRequest)?,
+        skip: page.unwrap_or(0).saturating_mul(config.page_size),
+        limit: config.page_size,
+        after: None,
+        hidden: false,
+        broken: broken.unwrap_or(true),
+    };
 
-    let sort = get_sort(sort.unwrap_or("new")).ok_or(Status::BadRequest)?;
-    let query = get_search(search);
-    let options = Some(
-        FindOptions::builder()
-            .sort(Some(sort))
-            .skip(page.unwrap_or(0))
-            .limit(20)
-            .build(),
-    );
-
-    let cursor = sc!(mods.find(query, options).await, Status::InternalServerError);
-    let mods: Vec<ModEntry> = sc!(cursor.try_collect().await, Status::InternalServerError);
+    let mods = sc!(mods.list(&query).await, Status::InternalServerError);
     let mods_json: Vec<Value> = mods.iter().map(super::expose_as_json).collect();
 
     Ok(json!(mods_json))
 }
 
-fn get_search(search: Option<&str>) -> mongodb::bson::Document {
-    match search {
-        Some(s) => doc! {
-            "$text": {
-                "$search": s,
-                "$caseSensitive": false,
-                "$diacriticSensitive": false
-            }
-        },
-        None => doc! {},
+pub(super) fn get_tags(tags: Option<&str>) -> Option<Vec<String>> {
+    match tags {
+        Some(tags) => crate::tags::parse(tags),
+        None => Some(Vec::new()),
+    }
+}
+
+pub(super) fn get_game(game: Option<&str>) -> Option<Option<i64>> {
+    match game {
+        Some(game) => crate::compat::parse_version(game)
+            .and_then(|v| crate::compat::pack(&v))
+            .map(Some),
+        None => Some(None),
     }
 }
 
-fn get_sort(sort: &str) -> Option<mongodb::bson::Document> {
+pub(super) fn get_sort(sort: &str, search: Option<&str>) -> Option<Sort> {
     match sort {
-        "new" => Some(doc! { "updated": -1 }),
-        "old" => Some(doc! { "updated": 1 }),
-        "most-downloads" => Some(doc! { "downloads": -1  }),
-        "least-downloads" => Some(doc! { "downloads": 1 }),
+        // Without a search, everything is equally relevant
+        "relevance" if search.is_none() => Some(Sort::New),
+        "relevance" => Some(Sort::Relevance),
+        "name" => Some(Sort::Name),
+        "published-new" => Some(Sort::PublishedNew),
+        "published-old" => Some(Sort::PublishedOld),
+        "trending" => Some(Sort::Trending),
+        "new" => Some(Sort::New),
+        "old" => Some(Sort::Old),
+        "most-downloads" => Some(Sort::MostDownloads),
+        "least-downloads" => Some(Sort::LeastDownloads),
         _ => None,
     }
 }
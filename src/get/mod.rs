@@ -1,14 +1,34 @@
 use crate::prelude::*;
-use rocket::serde::json::{serde_json::json, Value};
+use rocket::{
+    http::Status,
+    serde::json::{serde_json::json, Value},
+};
 
 pub(crate) mod index;
 pub(crate) mod many_mods;
+pub(crate) mod list_mods;
 pub(crate) mod one_mod;
 pub(crate) mod count_mods;
+pub(crate) mod suggest;
+pub(crate) mod tags;
+pub(crate) mod versions;
+pub(crate) mod resolve;
+pub(crate) mod download;
+pub(crate) mod icon;
+pub(crate) mod audit;
 
-type ApiResponse = Result<Value, rocket::http::Status>;
+type ApiResponse = Result<Value, Status>;
 
-fn expose_as_json(entry: &ModEntry) -> Value {
+async fn find_entry(owner: &str, name: &str, mods: &Mods) -> Result<ModEntry, Status> {
+    let full_name = format!("{owner}/{name}");
+    let entry = sc!(mods.get(&full_name).await, Status::InternalServerError);
+
+    entry
+        .filter(|e| e.deleted.is_none())
+        .ok_or(Status::NotFound)
+}
+
+pub(crate) fn expose_as_json(entry: &ModEntry) -> Value {
     let (owner, name) = entry.id.split_once('/').unwrap_or(("no-name", &entry.id));
     json!({
         "name": name,
@@ -18,7 +38,38 @@ fn expose_as_json(entry: &ModEntry) -> Value {
         "description": &entry.info.description,
         "homepage": &entry.info.homepage,
         "version": &entry.info.version,
-        "icon": &entry.info.icon,
-        "binary": &entry.info.binary
+        // Served from rdb's copy, which falls back to the original for older mods
+        "icon": format!("https://rdb.dual-iron.xyz/mods/{owner}/{name}/icon.png"),
+        "icon_source": &entry.info.icon,
+        "binary": &entry.info.binary,
+        "sha256": &entry.info.sha256,
+        "size": entry.info.size,
+        "files": entry.info.file_list(name),
+        "dependencies": &entry.info.dependencies,
+        "tags": &entry.info.tags,
+        "game_versions": &entry.info.game_versions,
+        "loader_version": &entry.info.loader_version,
+        "last_checked": entry.last_checked,
+        "broken": entry.broken,
+        "withdrawn": entry.withdrawn,
+        "prerelease": entry.current_prerelease().map(|r| expose_release_as_json(name, r))
+    })
+}
+
+fn expose_release_as_json(name: &str, release: &Release) -> Value {
+    json!({
+        "published": release.published,
+        "description": &release.info.description,
+        "homepage": &release.info.homepage,
+        "version": &release.info.version,
+        "icon": &release.info.icon,
+        "binary": &release.info.binary,
+        "sha256": &release.info.sha256,
+        "size": release.info.size,
+        "files": release.info.file_list(name),
+        "dependencies": &release.info.dependencies,
+        "tags": &release.info.tags,
+        "game_versions": &release.info.game_versions,
+        "loader_version": &release.info.loader_version
     })
 }
@@ -1,15 +1,9 @@
 use crate::{get::ApiResponse, prelude::*};
-use mongodb::bson::doc;
-use rocket::{http::Status, State};
+use rocket::State;
 
 #[rocket::get("/<owner>/<name>")]
 pub(crate) async fn one_mod(owner: &str, name: &str, mods: &State<Mods>) -> ApiResponse {
-    let full_name = format!("{owner}/{name}");
-    let q = doc! { "_id": full_name };
-    let entry = sc!(mods.find_one(q, None).await, Status::InternalServerError);
+    let entry = super::find_entry(owner, name, mods).await?;
 
-    match entry {
-        Some(m) => Ok(super::expose_as_json(&m)),
-        None => Err(Status::NotFound),
-    }
+    Ok(super::expose_as_json(&entry))
 }
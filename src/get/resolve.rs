@@ -0,0 +1,170 @@
+use crate::prelude::*;
+use rocket::{
+    http::Status,
+    response::status::Custom,
+    serde::json::{serde_json::json, Value},
+    State,
+};
+use semver::{Version, VersionReq};
+use std::collections::BTreeMap;
+
+// Selecting a version can pull in new requirements, so resolution repeats until nothing changes
+const MAX_ROUNDS: usize = 32;
+
+type ResolveResponse = Result<Value, Custom<Value>>;
+
+#[rocket::get("/<owner>/<name>/resolve?<version>")]
+pub(crate) async fn resolve(
+    owner: &str,
+    name: &str,
+    version: Option<&str>,
+    mods: &State<Mods>,
+) -> ResolveResponse {
+    let root = super::find_entry(owner, name, mods)
+        .await
+        .map_err(|s| Custom(s, json!({ "error": "not-found" })))?;
+
+    let root_release = match version {
+        Some(v) => {
+            let v = v.trim_start_matches(['v', 'V']);
+            root.history()
+                .into_iter()
+                .find(|r| r.info.version == v)
+                .ok_or_else(|| Custom(Status::NotFound, json!({ "error": "not-found" })))?
+        }
+        None => root.history().pop().expect("history is never empty"),
+    };
+
+    let mut resolver = Resolver {
+        mods,
+        entries: BTreeMap::new(),
+    };
+    resolver.entries.insert(root.id.clone(), Some(root));
+
+    let selected = resolver.resolve(owner, name, root_release).await?;
+    let install: Vec<Value> = selected
+        .iter()
+        .map(|(id, release)| {
+            let (owner, name) = id.split_once('/').unwrap_or(("no-name", id));
+            json!({
+                "name": name,
+                "owner": owner,
+                "version": &release.info.version,
+                "binary": &release.info.binary,
+                "files": release.info.file_list(name)
+            })
+        })
+        .collect();
+
+    Ok(json!(install))
+}
+
+struct Resolver<'a> {
+    mods: &'a Mods,
+    // Cache of every entry looked up so far, including ones that don't exist
+    entries: BTreeMap<String, Option<ModEntry>>,
+}
+
+impl Resolver<'_> {
+    async fn resolve(
+        &mut self,
+        owner: &str,
+        name: &str,
+        root: Release,
+    ) -> Result<BTreeMap<String, Release>, Custom<Value>> {
+        let root_id = format!("{owner}/{name}");
+        let mut selected = BTreeMap::from([(root_id.clone(), root.clone())]);
+
+        for _ in 0..MAX_ROUNDS {
+            // Gather every requirement imposed by the currently selected releases
+            let mut constraints: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+            for (id, release) in &selected {
+                for (dep, req) in &release.info.dependencies {
+                    constraints
+                        .entry(dep.clone())
+                        .or_default()
+                        .push((id.clone(), req.clone()));
+                }
+            }
+
+            let mut next = BTreeMap::from([(root_id.clone(), root.clone())]);
+            for (dep, reqs) in constraints {
+                let candidates = if dep == root_id {
+                    vec![root.clone()]
+                } else {
+                    self.history(&dep).await?
+                };
+
+                match pick(&candidates, &reqs) {
+                    Some(release) => next.insert(dep, release),
+                    None => return Err(conflict(&dep, &reqs, &candidates)),
+                };
+            }
+
+            let unchanged = next.len() == selected.len()
+                && next
+                    .iter()
+                    .zip(&selected)
+                    .all(|(a, b)| a.0 == b.0 && a.1.info.version == b.1.info.version);
+
+            if unchanged {
+                return Ok(next);
+            }
+            selected = next;
+        }
+
+        Err(Custom(
+            Status::Conflict,
+            json!({ "error": "unstable", "message": "Dependency resolution did not settle." }),
+        ))
+    }
+
+    async fn history(&mut self, id: &str) -> Result<Vec<Release>, Custom<Value>> {
+        if !self.entries.contains_key(id) {
+            let entry = sc!(
+                self.mods.get(id).await,
+                Custom(Status::InternalServerError, json!({ "error": "internal" }))
+            );
+            let entry = entry.filter(|e| e.deleted.is_none());
+            self.entries.insert(id.to_owned(), entry);
+        }
+
+        Ok(self.entries[id]
+            .as_ref()
+            .map(|e| e.history())
+            .unwrap_or_default())
+    }
+}
+
+// Picks the newest release that satisfies every requirement
+fn pick(candidates: &[Release], reqs: &[(String, String)]) -> Option<Release> {
+    let reqs: Vec<VersionReq> = reqs
+        .iter()
+        .filter_map(|(_, req)| VersionReq::parse(req).ok())
+        .collect();
+
+    candidates
+        .iter()
+        .filter_map(|r| Some((Version::parse(&r.info.version).ok()?, r)))
+        .filter(|(v, _)| reqs.iter().all(|req| req.matches(v)))
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, r)| r.clone())
+}
+
+fn conflict(dep: &str, reqs: &[(String, String)], candidates: &[Release]) -> Custom<Value> {
+    let requirements: Vec<Value> = reqs
+        .iter()
+        .map(|(by, req)| json!({ "required_by": by, "requirement": req }))
+        .collect();
+    let available: Vec<&str> = candidates.iter().map(|r| r.info.version.as_str()).collect();
+
+    Custom(
+        Status::Conflict,
+        json!({
+            "error": if candidates.is_empty() { "missing" } else { "conflict" },
+            "mod": dep,
+            "requirements": requirements,
+            "available": available
+        }),
+    )
+}
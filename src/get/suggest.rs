@@ -0,0 +1,34 @@
+use super::ApiResponse;
+use crate::prelude::*;
+use rocket::{http::Status, serde::json::Value, State};
+
+// Enough to fill a dropdown
+const SUGGESTIONS: u64 = 10;
+const MAX_QUERY: usize = 100;
+// The `search` field only has prefixes this long or longer
+const MIN_WORD: usize = 3;
+
+#[rocket::get("/suggest?<q>")]
+pub(crate) async fn suggest(q: &str, mods: &State<Mods>) -> ApiResponse {
+    if q.len() > MAX_QUERY {
+        return Err(Status::BadRequest);
+    }
+
+    // Split the same way as the `search` field, so each word is looked up as a prefix.
+    // Words too short to have been indexed are left out rather than matching nothing.
+    let words: Vec<String> = q
+        .split(|c: char| !c.is_alphanumeric() && c != '.')
+        .filter(|w| w.chars().count() >= MIN_WORD)
+        .map(str::to_lowercase)
+        .collect();
+
+    if words.is_empty() {
+        return Ok(Value::Array(Vec::new()));
+    }
+
+    let ids = sc!(
+        mods.suggest(&words, SUGGESTIONS).await,
+        Status::InternalServerError
+    );
+    Ok(ids.into())
+}
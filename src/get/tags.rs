@@ -0,0 +1,20 @@
+use super::ApiResponse;
+use crate::{prelude::*, tags::TAGS};
+use rocket::{
+    http::Status,
+    serde::json::{serde_json::json, Value},
+    State,
+};
+
+#[rocket::get("/tags")]
+pub(crate) async fn tags(mods: &State<Mods>) -> ApiResponse {
+    let counts = sc!(mods.tag_counts().await, Status::InternalServerError);
+
+    // Every tag is listed, even unused ones, so clients can offer all of them
+    let tags: Vec<Value> = TAGS
+        .iter()
+        .map(|&tag| json!({ "tag": tag, "count": counts.get(tag).copied().unwrap_or(0) }))
+        .collect();
+
+    Ok(json!(tags))
+}
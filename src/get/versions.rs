@@ -0,0 +1,37 @@
+use crate::{get::ApiResponse, prelude::*};
+use rocket::{
+    http::Status,
+    serde::json::{serde_json::json, Value},
+    State,
+};
+
+#[rocket::get("/<owner>/<name>/versions")]
+pub(crate) async fn versions(owner: &str, name: &str, mods: &State<Mods>) -> ApiResponse {
+    let entry = super::find_entry(owner, name, mods).await?;
+    let releases: Vec<Value> = entry
+        .history()
+        .iter()
+        .rev()
+        .map(|r| super::expose_release_as_json(name, r))
+        .collect();
+
+    Ok(json!(releases))
+}
+
+#[rocket::get("/<owner>/<name>/versions/<version>")]
+pub(crate) async fn version(
+    owner: &str,
+    name: &str,
+    version: &str,
+    mods: &State<Mods>,
+) -> ApiResponse {
+    let entry = super::find_entry(owner, name, mods).await?;
+    let version = version.trim_start_matches(['v', 'V']);
+
+    entry
+        .history()
+        .iter()
+        .find(|r| r.info.version == version)
+        .map(|r| super::expose_release_as_json(name, r))
+        .ok_or(Status::NotFound)
+}
@@ -1,6 +1,17 @@
+use crate::config::RateLimit;
+use hmac::{Hmac, Mac};
+use rocket::tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use rocket::{
+    data::{self, FromData, Limits},
+    http::Status,
     request::{FromRequest, Outcome},
-    Request,
+    serde::{json::serde_json, DeserializeOwned},
+    Data, Request,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
 };
 
 macro_rules! event_type_guard {
@@ -14,7 +25,7 @@ macro_rules! event_type_guard {
             async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
                 match req.headers().get_one("X-GitHub-Event") {
                     Some($header_name) => Outcome::Success($type_name),
-                    _ => Outcome::Forward(()),
+                    _ => Outcome::Forward(Status::NotFound),
                 }
             }
         }
@@ -23,3 +34,165 @@ macro_rules! event_type_guard {
 
 event_type_guard!(RelGuard, "release");
 event_type_guard!(PingGuard, "ping");
+
+/// The token that admin requests must present. Admin routes are disabled without one.
+pub struct AdminToken(pub Option<String>);
+
+/// Succeeds if the request has an `Authorization: Bearer <token>` header with the admin token.
+pub struct AdminGuard;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminGuard {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let token = req
+            .rocket()
+            .state::<AdminToken>()
+            .and_then(|t| t.0.as_ref());
+        let given = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "));
+
+        // Comparing digests keeps the comparison from leaking how much of the token matched
+        match (token, given) {
+            (Some(token), Some(given))
+                if Sha256::digest(token.as_bytes()) == Sha256::digest(given.as_bytes()) =>
+            {
+                Outcome::Success(AdminGuard)
+            }
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// A JSON payload kept alongside its raw body and the `X-Hub-Signature-256` header, if any.
+pub struct Signed<T> {
+    pub payload: T,
+    body: Vec<u8>,
+    signature: Option<Vec<u8>>,
+}
+
+impl<T> Signed<T> {
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// Checks the signature against an HMAC of the raw body in constant time.
+    pub fn verify(&self, secret: &str) -> bool {
+        let signature = match &self.signature {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(&self.body);
+        mac.verify_slice(signature).is_ok()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for Signed<T> {
+    type Error = &'static str;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = req.limits().get("json").unwrap_or(Limits::JSON);
+        let body = match data.open(limit).into_bytes().await {
+            Ok(b) if b.is_complete() => b.into_inner(),
+            Ok(_) => return data::Outcome::Error((Status::PayloadTooLarge, "Payload too large.")),
+            Err(_) => return data::Outcome::Error((Status::BadRequest, "Failed to read payload.")),
+        };
+
+        // Malformed signatures are treated like wrong ones rather than missing ones
+        let signature = req
+            .headers()
+            .get_one("X-Hub-Signature-256")
+            .map(|s| s.strip_prefix("sha256=").and_then(|h| hex::decode(h).ok()))
+            .map(Option::unwrap_or_default);
+
+        match serde_json::from_slice(&body) {
+            Ok(payload) => data::Outcome::Success(Signed {
+                payload,
+                body,
+                signature,
+            }),
+            Err(_) => data::Outcome::Error((Status::UnprocessableEntity, "Malformed payload.")),
+        }
+    }
+}
+
+/// Counts writes per IP, or per repository for webhook deliveries, in fixed windows.
+pub struct RateLimiter {
+    limit: RateLimit,
+    hits: Mutex<HashMap<String, (i64, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request from whoever `key` names, like an IP address, and returns whether it's
+    /// within the limit.
+    pub fn allow(&self, key: &str) -> bool {
+        if self.limit.requests == 0 {
+            return true;
+        }
+
+        let now = crate::serde::timestamp();
+        let window = self.limit.window as i64;
+        let mut hits = self.hits.lock().expect("rate limiter poisoned");
+
+        // Forget keys whose windows are over so the map doesn't grow forever
+        if hits.len() > 10_000 {
+            hits.retain(|_, (start, _)| now - *start < window);
+        }
+
+        let (start, count) = hits.entry(key.to_owned()).or_insert((now, 0));
+        if now - *start >= window {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.limit.requests
+    }
+}
+
+/// Caps how many webhook releases are synced in the background at once.
+pub struct Syncs(Arc<Semaphore>);
+
+impl Syncs {
+    pub fn new(max: usize) -> Self {
+        Self(Arc::new(Semaphore::new(max)))
+    }
+
+    /// Takes a slot for one sync, which is freed when the permit is dropped. Returns `None` if
+    /// every slot is taken.
+    pub fn reserve(&self) -> Option<OwnedSemaphorePermit> {
+        self.0.clone().try_acquire_owned().ok()
+    }
+}
+
+/// Fails with 429 Too Many Requests once the client's IP runs out of writes.
+pub struct Throttle;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Throttle {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let limiter = req.rocket().state::<RateLimiter>();
+
+        match (limiter, req.client_ip()) {
+            (Some(limiter), Some(ip)) if !limiter.allow(&ip.to_string()) => {
+                Outcome::Error((Status::TooManyRequests, ()))
+            }
+            _ => Outcome::Success(Throttle),
+        }
+    }
+}
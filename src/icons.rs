@@ -0,0 +1,141 @@
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+pub(crate) const SIZE: u32 = 128;
+
+/// Checks that `data` is a 128x128 PNG and re-encodes it as plain 8-bit RGBA, which drops
+/// metadata and anything else clients don't need.
+pub(crate) fn normalise(data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    const INVALID: &str = "The icon must be a valid PNG file.";
+
+    let mut decoder = Decoder::new(data);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|_| INVALID)?;
+
+    // Checked before decoding so huge images are never decompressed
+    let info = reader.info();
+    if info.width != SIZE || info.height != SIZE {
+        return Err("The icon must be 128x128 pixels.");
+    }
+
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels).map_err(|_| INVALID)?;
+    let pixels = &pixels[..frame.buffer_size()];
+
+    let rgba: Vec<u8> = match frame.color_type {
+        ColorType::Rgba => pixels.to_vec(),
+        ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        ColorType::Grayscale => pixels.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+        // Palettes are expanded by the decoder
+        ColorType::Indexed => return Err(INVALID),
+    };
+
+    encode(&rgba)
+}
+
+/// A fully transparent icon.
+pub(crate) fn blank() -> Vec<u8> {
+    encode(&vec![0; (SIZE * SIZE * 4) as usize]).expect("failed to encode blank icon")
+}
+
+fn encode(rgba: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut out = Vec::new();
+    let mut encoder = Encoder::new(&mut out, SIZE, SIZE);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut w| w.write_image_data(rgba))
+        .map_err(|_| "The icon couldn't be processed.")?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Encodes a square image of `size` pixels, each one `pixel`
+    fn png(size: u32, color: ColorType, pixel: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = Encoder::new(&mut out, size, size);
+        encoder.set_color(color);
+        encoder.set_depth(BitDepth::Eight);
+        if color == ColorType::Indexed {
+            encoder.set_palette(vec![0, 0, 0, 10, 20, 30]);
+            encoder.set_trns(vec![255, 128]);
+        }
+
+        let data = pixel.repeat((size * size) as usize);
+        encoder
+            .write_header()
+            .and_then(|mut w| w.write_image_data(&data))
+            .unwrap();
+        out
+    }
+
+    // The first pixel of a normalised icon, after checking that it's 8-bit RGBA
+    fn first_pixel(icon: &[u8]) -> [u8; 4] {
+        let mut reader = Decoder::new(icon).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((frame.width, frame.height), (SIZE, SIZE));
+        assert_eq!(frame.color_type, ColorType::Rgba);
+        assert_eq!(frame.bit_depth, BitDepth::Eight);
+        [pixels[0], pixels[1], pixels[2], pixels[3]]
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(
+            normalise(b"GIF89a not a png"),
+            Err("The icon must be a valid PNG file.")
+        );
+        assert_eq!(normalise(&[]), Err("The icon must be a valid PNG file."));
+
+        // Cut off partway through the pixels
+        let icon = png(SIZE, ColorType::Rgba, &[1, 2, 3, 4]);
+        assert_eq!(
+            normalise(&icon[..icon.len() / 2]),
+            Err("The icon must be a valid PNG file.")
+        );
+    }
+
+    #[test]
+    fn rejects_other_sizes() {
+        for size in [64, 127, 129, 256] {
+            assert_eq!(
+                normalise(&png(size, ColorType::Rgba, &[1, 2, 3, 4])),
+                Err("The icon must be 128x128 pixels.")
+            );
+        }
+    }
+
+    #[test]
+    fn converts_every_color_type() {
+        let cases: [(ColorType, &[u8], [u8; 4]); 5] = [
+            (ColorType::Rgba, &[10, 20, 30, 40], [10, 20, 30, 40]),
+            (ColorType::Rgb, &[10, 20, 30], [10, 20, 30, 255]),
+            (ColorType::GrayscaleAlpha, &[50, 60], [50, 50, 50, 60]),
+            (ColorType::Grayscale, &[50], [50, 50, 50, 255]),
+            (ColorType::Indexed, &[1], [10, 20, 30, 128]),
+        ];
+
+        for (color, pixel, rgba) in cases {
+            let icon = normalise(&png(SIZE, color, pixel)).unwrap();
+            assert_eq!(first_pixel(&icon), rgba, "{color:?}");
+        }
+    }
+
+    #[test]
+    fn blank_is_transparent() {
+        assert_eq!(first_pixel(&blank()), [0, 0, 0, 0]);
+    }
+}
@@ -1,4 +1,6 @@
-use mongodb::bson::doc;
+use crate::store::TRENDING_WINDOW;
+use mongodb::{bson::doc, options::IndexOptions, Collection};
+use std::time::Duration;
 
 macro_rules! index {
     ($collection:expr, $doc:expr) => {
@@ -11,6 +13,34 @@ macro_rules! index {
 
 pub(crate) async fn add_indexes<T>(collection: &mongodb::Collection<T>) {
     index!(collection, doc! { "search": "text" });
+    index!(collection, doc! { "terms.term": 1 });
+    index!(collection, doc! { "info.tags": 1 });
+    index!(
+        collection,
+        doc! { "game_range.min": 1, "game_range.max": 1 }
+    );
     index!(collection, doc! { "downloads": 1 });
     index!(collection, doc! { "updated": 1 });
+    index!(collection, doc! { "published": 1 });
+    index!(collection, doc! { "trending": 1 });
+}
+
+pub(crate) async fn add_download_indexes<T>(collection: &Collection<T>) {
+    // Download records are kept for as long as they count towards trending
+    let ttl = IndexOptions::builder()
+        .expire_after(Duration::from_secs(TRENDING_WINDOW as u64))
+        .build();
+    let model = mongodb::IndexModel::builder()
+        .keys(doc! { "created": 1 })
+        .options(ttl)
+        .build();
+
+    collection
+        .create_index(model, None)
+        .await
+        .expect("failed to create an index");
+}
+
+pub(crate) async fn add_audit_indexes<T>(collection: &mongodb::Collection<T>) {
+    index!(collection, doc! { "mod_id": 1, "time": -1 });
 }
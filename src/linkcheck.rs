@@ -0,0 +1,47 @@
+use crate::{fetch::Fetcher, prelude::*};
+use rocket::tokio::time::{sleep, Duration};
+use std::sync::Arc;
+
+// How often to look for mods that are due for a check
+const PERIOD: Duration = Duration::from_secs(10 * 60);
+// Spaces out requests so hosts aren't hammered
+const PACE: Duration = Duration::from_millis(250);
+
+/// Re-checks each mod's binary and icon once per `interval`, forever.
+/// Only mods that are due are checked, so restarting the server doesn't start over.
+pub(crate) async fn run(mods: Mods, client: Arc<dyn Fetcher>, interval: Duration) {
+    loop {
+        check_due(&mods, &*client, interval.as_secs() as i64).await;
+        sleep(PERIOD.min(interval)).await;
+    }
+}
+
+async fn check_due(mods: &Mods, client: &dyn Fetcher, interval: i64) {
+    let entries = match mods.all().await {
+        Ok(entries) => entries,
+        Err(e) => {
+            dbg!(e);
+            return;
+        }
+    };
+
+    let now = timestamp();
+    let due = entries
+        .iter()
+        .filter(|e| e.deleted.is_none())
+        .filter(|e| !matches!(e.last_checked, Some(t) if now - t < interval));
+
+    for entry in due {
+        let info = &entry.info;
+        let (_, name) = entry.id.split_once('/').unwrap_or(("", &entry.id));
+        let mut broken = !info.icon.is_empty() && client.check(&info.icon).await.is_err();
+        for file in info.file_list(name) {
+            broken = broken || client.check(&file.url).await.is_err();
+        }
+
+        if let Err(e) = mods.set_link_status(&entry.id, timestamp(), broken).await {
+            dbg!(e);
+        }
+        sleep(PACE).await;
+    }
+}
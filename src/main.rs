@@ -1,40 +1,137 @@
+mod admin;
+mod compat;
+mod config;
+mod delete;
+mod fetch;
 mod get;
 mod guards;
+mod icons;
 mod indexes;
+mod linkcheck;
+mod manifest;
 mod post;
 mod prelude;
+mod providers;
+mod search;
+mod secrets;
 mod serde;
+mod store;
+mod tags;
+mod trending;
+
+#[cfg(test)]
+mod tests;
 
-use mongodb::{options::ClientOptions, Client};
 use rocket::*;
 
-#[launch]
-async fn rocket() -> _ {
-    println!("Connecting MongoDB client");
+#[rocket::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    match &args[..] {
+        [] => {
+            if let Err(e) = rocket(config).await.launch().await {
+                eprintln!("Rocket failed to launch: {e}");
+                std::process::exit(1);
+            }
+        }
+        [flag] if flag == "--check-config" => print!("{config}"),
+        [cmd, from, to] if cmd == "migrate" => {
+            let from = store::open(from, &config).await;
+            let to = store::open(to, &config).await;
+            let count = store::migrate(&*from, &*to)
+                .await
+                .expect("failed to migrate store");
+            println!("Copied {count} mods");
+        }
+        _ => {
+            eprintln!("usage: rdb [--check-config | migrate <from DB_URL> <to DB_URL>]");
+            std::process::exit(2);
+        }
+    }
+}
+
+async fn rocket(config: config::Config) -> Rocket<Build> {
+    let mods: prelude::Mods = store::open(&config.db_url, &config).await.into();
+    let keys = secrets::HookKeys::new(&config.secret_key);
 
-    let conn = std::env::var("DB_URL").expect("no `DB_URL` env var");
-    let options = ClientOptions::parse(conn)
-        .await
-        .expect("invalid connection url");
+    secrets::migrate(&mods, &keys).await;
+    search::migrate(&mods).await;
 
-    let client = Client::with_options(options).expect("failed to connect MongoDB client");
-    let mods: prelude::Mods = client.database("test2").collection("mods");
+    if config.link_check_interval > 0 {
+        let interval = std::time::Duration::from_secs(config.link_check_interval);
+        let client = fetch::client(config.fetcher);
+        rocket::tokio::spawn(linkcheck::run(mods.clone(), client, interval));
+    }
 
-    indexes::add_indexes(&mods).await;
+    rocket::tokio::spawn(trending::run(mods.clone()));
 
     println!("Launching Rocket");
 
-    build()
+    app(mods, keys, config)
+}
+
+/// Builds the app around a store, so it can run against any backend.
+pub(crate) fn app(
+    mods: prelude::Mods,
+    keys: secrets::HookKeys,
+    config: config::Config,
+) -> Rocket<Build> {
+    let admin = prelude::AdminToken(config.admin_token.clone());
+    let limiter = prelude::RateLimiter::new(config.rate_limit);
+    let syncs = prelude::Syncs::new(config.max_syncs);
+    let providers =
+        providers::Providers::new(&config.binary_providers).expect("invalid binary providers");
+    let fetch = fetch::Fetch::new(fetch::client(config.fetcher), config.max_binary_size);
+
+    custom(config::Config::figment())
         .manage(mods)
-        .mount("/", routes![get::index::index])
+        .manage(keys)
+        .manage(admin)
+        .manage(limiter)
+        .manage(syncs)
+        .manage(providers)
+        .manage(fetch)
+        .manage(config)
+        .mount("/", routes![get::index::index, get::tags::tags])
+        .mount(
+            "/admin",
+            routes![
+                admin::delete_mod,
+                admin::restore_mod,
+                admin::visibility,
+                admin::reset_secret,
+                admin::transfer,
+                admin::recent
+            ],
+        )
         .mount("/github", routes![post::hook::ping, post::hook::release])
         .mount(
             "/mods",
             routes![
                 get::one_mod::one_mod,
                 get::many_mods::many_mods,
+                get::list_mods::list_mods,
                 get::count_mods::count_mods,
-                post::submit::submit
+                get::suggest::suggest,
+                get::versions::versions,
+                get::versions::version,
+                get::resolve::resolve,
+                get::download::download,
+                get::icon::icon,
+                get::audit::audit,
+                post::submit::submit,
+                post::secret::rotate_secret,
+                post::visibility::visibility,
+                delete::one_mod::delete_mod
             ],
         )
 }
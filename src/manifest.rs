@@ -0,0 +1,128 @@
+use crate::prelude::*;
+use rocket::serde::json::serde_json;
+
+const NAMES: [&str; 2] = ["rdb.json", "rdb.toml"];
+const INVALID: &str = "The manifest must be JSON or TOML, and can only have the fields `binary`, `icon`, `description`, `tags`, `dependencies`, `game_versions`, `loader_version` and `files`.";
+
+/// Whether a release asset is a manifest rather than part of the mod.
+pub(crate) fn is_manifest(asset: &str) -> bool {
+    NAMES.contains(&asset)
+}
+
+/// Finds a release's manifest. Looks for an `rdb.json` or `rdb.toml` release asset, then a code
+/// block labelled with either name in the release's body, then either file at the top of the
+/// repository at the release's tag.
+pub(crate) async fn find(
+    rel: &GHRelPayload,
+    fetch: &Fetch,
+) -> Result<Option<Manifest>, &'static str> {
+    if let Some(asset) = rel.release.assets.iter().find(|a| is_manifest(&a.name)) {
+        let body = fetch
+            .manifest(&asset.browser_download_url)
+            .await?
+            .ok_or("The manifest asset couldn't be downloaded.")?;
+        return parse(&asset.name, &body).map(Some);
+    }
+
+    if let Some((name, block)) = code_block(rel.release.body.as_deref().unwrap_or_default()) {
+        return parse(name, block.as_bytes()).map(Some);
+    }
+
+    for name in NAMES {
+        let url = format!(
+            "https://raw.githubusercontent.com/{}/{}/{name}",
+            rel.repository.full_name, rel.release.tag_name
+        );
+        if let Some(body) = fetch.manifest(&url).await? {
+            return parse(name, &body).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Resolves a manifest's icon to a URL. Paths are relative to the top of the repository at the
+/// release's tag.
+pub(crate) fn icon_url(rel: &GHRelPayload, icon: &str) -> Result<String, &'static str> {
+    if icon.starts_with("https://") {
+        return Ok(icon.to_owned());
+    }
+
+    let path = icon.trim_start_matches("./");
+    let valid = !path.is_empty()
+        && !path.starts_with('/')
+        && path.split('/').all(|part| !part.is_empty() && part != "..");
+
+    match valid {
+        true => Ok(format!(
+            "https://raw.githubusercontent.com/{}/{}/{path}",
+            rel.repository.full_name, rel.release.tag_name
+        )),
+        false => Err("The manifest's icon must be a URL or a path inside the repository."),
+    }
+}
+
+fn parse(name: &str, body: &[u8]) -> Result<Manifest, &'static str> {
+    let manifest = if name.ends_with(".toml") {
+        std::str::from_utf8(body)
+            .ok()
+            .and_then(|s| toml::from_str(s).ok())
+    } else {
+        serde_json::from_slice(body).ok()
+    };
+    manifest.ok_or(INVALID)
+}
+
+// The first fenced code block whose info string is a manifest's name, like ```` ```rdb.toml ````
+fn code_block(body: &str) -> Option<(&'static str, String)> {
+    let mut lines = body.lines();
+    let name = lines.find_map(|line| {
+        let info = line.trim().strip_prefix("```")?.trim();
+        NAMES.into_iter().find(|name| *name == info)
+    })?;
+
+    let block: Vec<&str> = lines.take_while(|line| line.trim() != "```").collect();
+    Some((name, block.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_labelled_code_blocks() {
+        let body = "Notes\n\n```json\n{}\n```\n\n```rdb.toml\ntags = [\"creature\"]\nbinary = \"A.dll\"\n```\n";
+        assert_eq!(
+            code_block(body),
+            Some((
+                "rdb.toml",
+                "tags = [\"creature\"]\nbinary = \"A.dll\"".to_owned()
+            ))
+        );
+
+        let crlf = "```rdb.json\r\n{ \"binary\": \"A.dll\" }\r\n```\r\n";
+        assert_eq!(
+            code_block(crlf),
+            Some(("rdb.json", "{ \"binary\": \"A.dll\" }".to_owned()))
+        );
+
+        assert_eq!(code_block("```json\n{}\n```"), None);
+        assert_eq!(code_block(""), None);
+    }
+
+    #[test]
+    fn parses_and_validates() {
+        let toml = parse(
+            "rdb.toml",
+            b"binary = \"A.dll\"\n[dependencies]\n\"a/b\" = \"^1\"",
+        )
+        .unwrap();
+        assert_eq!(toml.binary.as_deref(), Some("A.dll"));
+        assert_eq!(toml.dependencies.unwrap()["a/b"], "^1");
+
+        let json = parse("rdb.json", br#"{ "game_versions": ">=1.9" }"#).unwrap();
+        assert_eq!(json.game_versions.as_deref(), Some(">=1.9"));
+
+        assert!(parse("rdb.json", br#"{ "bin": "A.dll" }"#).is_err());
+        assert!(parse("rdb.toml", b"not toml").is_err());
+    }
+}
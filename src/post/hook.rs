@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use rocket::{http::Status, response::status::Custom, serde::json::Json, State};
+use std::net::IpAddr;
 
 #[rocket::post("/", data = "<data>", format = "application/json")]
 pub(crate) async fn ping(data: Json<GHPingPayload>, _e: PingGuard) -> Result<String, ()> {
@@ -12,7 +13,9 @@ pub(crate) async fn ping(data: Json<GHPingPayload>, _e: PingGuard) -> Result<Str
     }
 
     Ok(format!(
-        "Successfully connected to rdb! The next release you create or edit will be synced to rdb.
+        "Successfully connected to rdb! The next release you publish or edit will be synced to rdb.
+Prereleases are kept separate from your mod's current release, and drafts are ignored.
+Unpublishing or deleting your mod's current release marks it as withdrawn.
 
 The rdb entry fields will look like the following:
     name            {name}
@@ -22,8 +25,14 @@ The rdb entry fields will look like the following:
     homepage        {homepage}
     version         {{tag name}}
     binary          {{last release asset sorted alphabetically}}
+    files           {{every release asset}}
 
-The icon should be a 128x128 `icon.png` file located at your repository's top directory.
+The icon must be a 128x128 `icon.png` file located at your repository's top directory.
+To choose fields yourself, add an `rdb.json` or `rdb.toml` manifest as a release asset, as a code block
+labelled `rdb.json` or `rdb.toml` in the release notes, or at your repository's top directory:
+    {{ \"binary\": \"MyMod.dll\", \"icon\": \"assets/icon.png\", \"tags\": [\"creature\"], \"game_versions\": \">=1.9\" }}
+The webhook's secret must be your mod's secret, or releases will be rejected.
+The webhook only updates mods that are already in rdb. Submit your mod's first release to POST https://rdb.dual-iron.xyz/mods.
 
 Complete documentation of rdb: https://rdb.dual-iron.xyz
 "
@@ -31,48 +40,306 @@ Complete documentation of rdb: https://rdb.dual-iron.xyz
 }
 
 #[rocket::post("/?<secret>", data = "<data>", format = "application/json")]
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn release(
-    secret: String,
-    data: Json<GHRelPayload>,
+    secret: Option<String>,
+    data: Signed<GHRelPayload>,
+    ip: Option<IpAddr>,
     mods: &State<Mods>,
+    keys: &State<HookKeys>,
+    config: &State<Config>,
+    providers: &State<Providers>,
+    fetch: &State<Fetch>,
+    limiter: &State<RateLimiter>,
+    syncs: &State<Syncs>,
+    _t: Throttle,
     _e: RelGuard,
 ) -> SimpleResponse {
-    if data.0.action == "deleted" {
+    // GitHub sends `published` alongside `released` or `prereleased`, so only those submit
+    let withdrawing = match data.payload.action.as_str() {
+        "released" | "prereleased" | "edited" => false,
+        "unpublished" | "deleted" => true,
+        "created" | "published" => {
+            return Ok("Ignored. rdb submits releases from the `released` and `prereleased` deliveries that follow this one.")
+        }
+        _ => return Ok("Ignored. rdb doesn't handle this kind of release delivery."),
+    };
+    // Unpublished releases are drafts again, so they can still be withdrawn
+    if data.payload.release.draft && !withdrawing {
+        return Ok("Ignored. Draft releases are submitted once they're published.");
+    }
+
+    let secret = match authenticate(secret, &data, mods, keys, config).await {
+        Ok(secret) => secret,
+        Err(e) => {
+            let id = &data.payload.repository.full_name;
+            AuditEvent::new(id, "hook", "unauthorized", ip)
+                .record(mods)
+                .await;
+            return Err(e);
+        }
+    };
+    // Deliveries all come from GitHub's addresses, so they're also limited per repository. That's
+    // only checked once the delivery is authenticated, since anyone can name any repository.
+    if !limiter.allow(&format!("github:{}", data.payload.repository.full_name)) {
+        return Err(Custom(
+            Status::TooManyRequests,
+            "Too many deliveries for this repository. Try again later.",
+        ));
+    }
+    if withdrawing {
+        return withdraw(&data.payload, ip, mods).await;
+    }
+    // Editing a release's notes shouldn't resubmit it
+    if data.payload.action == "edited" && is_synced(&data.payload, mods).await {
+        return Ok("Ignored. This release is already synced to rdb.");
+    }
+
+    // GitHub gives up on deliveries after 10 seconds, which often isn't long enough to download
+    // every file, so those are fetched in the background once everything else checks out
+    let Some(permit) = syncs.reserve() else {
+        return Err(Custom(
+            Status::ServiceUnavailable,
+            "rdb is busy syncing other releases. Redeliver this one later.",
+        ));
+    };
+    let prerelease = data.payload.release.prerelease;
+    let submission = submission(data.payload, secret, ip, mods, fetch).await?;
+    let checked = match prerelease {
+        true => super::submit::check_prerelease(submission, ip, mods, providers).await?,
+        false => super::submit::check("hook", submission, ip, mods, providers).await?,
+    };
+
+    let (mods, keys, fetch) = (
+        mods.inner().clone(),
+        keys.inner().clone(),
+        fetch.inner().clone(),
+    );
+    rocket::tokio::spawn(async move {
+        let _permit = permit;
+        match prerelease {
+            true => super::submit::store_prerelease(checked, &mods, &fetch).await,
+            false => super::submit::store(checked, &mods, &keys, &fetch).await,
+        }
+    });
+
+    Ok("Accepted. The release's files are being checked, and it shows up on rdb once they are.")
+}
+
+// Builds a submission from the release and its manifest. Failures are audited.
+async fn submission(
+    rel: GHRelPayload,
+    secret: String,
+    ip: Option<IpAddr>,
+    mods: &Mods,
+    fetch: &Fetch,
+) -> Result<Submission, Custom<&'static str>> {
+    let mut event = AuditEvent::new(&rel.repository.full_name, "hook", "invalid", ip);
+    event.new_version = Some(rel.release.tag_name.clone());
+
+    // Authors can fill in or override fields with a manifest
+    let submission = match crate::manifest::find(&rel, fetch).await {
+        Ok(manifest) => extract_submission(rel, secret, manifest),
+        Err(e) => Err(e),
+    };
+    match submission {
+        Ok(submission) => Ok(submission),
+        Err(e) => {
+            event.record(mods).await;
+            Err(client_err(e))
+        }
+    }
+}
+
+// Marks the release withdrawn if it's the mod's current release or prerelease
+async fn withdraw(rel: &GHRelPayload, ip: Option<IpAddr>, mods: &Mods) -> SimpleResponse {
+    let id = &rel.repository.full_name;
+    let version = rel.release.tag_name.trim().trim_start_matches(['v', 'V']);
+
+    let entry = sc!(
+        mods.get(id).await,
+        server_err("The server encountered an error.")
+    );
+    let entry = match entry {
+        Some(e) if e.deleted.is_none() => e,
+        _ => return Err(Custom(Status::NotFound, "The mod doesn't exist.")),
+    };
+
+    let mut event = AuditEvent::new(id, "hook", "withdrawn", ip);
+    event.old_version = Some(version.to_owned());
+
+    if entry.info.version == version {
+        let withdrawn = sc!(
+            mods.withdraw(id, version).await,
+            server_err("The server encountered an error.")
+        );
+        if withdrawn {
+            event.record(mods).await;
+            return Ok(
+                "Withdrew the mod's current release. It stays withdrawn until the next release.",
+            );
+        }
+    } else if entry
+        .current_prerelease()
+        .is_some_and(|p| p.info.version == version)
+    {
+        sc!(
+            mods.set_prerelease(id, None).await,
+            server_err("The server encountered an error.")
+        );
+        event.outcome = "withdrawn-prerelease".to_owned();
+        event.record(mods).await;
+        return Ok("Withdrew the mod's prerelease.");
+    }
+
+    Ok("Ignored. Only a mod's current release or prerelease can be withdrawn.
+To delete your mod from rdb, send a request to DELETE https://rdb.dual-iron.xyz/mods/<owner>/<name>.")
+}
+
+// Whether the release is already the mod's current release or prerelease
+async fn is_synced(rel: &GHRelPayload, mods: &Mods) -> bool {
+    let version = rel.release.tag_name.trim().trim_start_matches(['v', 'V']);
+
+    match mods.get(&rel.repository.full_name).await {
+        Ok(Some(entry)) if entry.deleted.is_none() => {
+            entry.info.version == version
+                || entry
+                    .current_prerelease()
+                    .is_some_and(|p| p.info.version == version)
+        }
+        _ => false,
+    }
+}
+
+// Finds the mod's secret, which a delivery must prove knowledge of
+async fn authenticate(
+    query: Option<String>,
+    data: &Signed<GHRelPayload>,
+    mods: &Mods,
+    keys: &HookKeys,
+    config: &Config,
+) -> Result<String, Custom<&'static str>> {
+    // Secrets in URLs end up in proxy and delivery logs, so they're only read if allowed
+    if query.is_some() && !config.allow_query_secret {
         return Err(client_err(
-            "Deleted releases are ignored by rdb.
-To overwrite release information, submit a new release.
-To delete your mod from rdb, contact Dual (Discord ID 303617148411183105).",
+            "Remove `?secret=` from the webhook URL. Deliveries are checked with the webhook's secret instead.",
         ));
     }
 
-    let submission = extract_submission(data.0, secret).ok_or(Custom(
-        Status::BadRequest,
-        "Bad format. Did you have a release asset?",
-    ))?;
+    // Mods are added through `POST /mods`, so there's always a stored secret to check against
+    let entry = match mods.get(&data.payload.repository.full_name).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => {
+            return Err(Custom(
+                Status::NotFound,
+                "This mod isn't in rdb yet. Submit its first release to POST https://rdb.dual-iron.xyz/mods.",
+            ))
+        }
+        Err(e) => {
+            dbg!(e);
+            return Err(server_err("The server encountered an error."));
+        }
+    };
 
-    super::submit::submit(Json(submission), mods).await
+    if !data.is_signed() {
+        return match query {
+            Some(secret) if crate::secrets::verify(secret.clone(), entry.secret).await => {
+                Ok(secret)
+            }
+            Some(_) => Err(Custom(Status::Unauthorized, "The secret is incorrect.")),
+            None => Err(Custom(
+                Status::Unauthorized,
+                "Deliveries must be signed. Set the webhook's secret to your mod's secret.",
+            )),
+        };
+    }
+
+    let secret = entry
+        .hook_key
+        .and_then(|k| keys.open(&k))
+        .ok_or(server_err("The mod's secret can't be recovered."))?;
+    if data.verify(&secret) {
+        Ok(secret)
+    } else {
+        Err(Custom(Status::Unauthorized, "The signature is incorrect."))
+    }
 }
 
-fn extract_submission(mut rel: GHRelPayload, secret: String) -> Option<Submission> {
-    let (owner, name) = rel.repository.full_name.split_once('/')?;
-    let binary = rel.release.assets.pop()?.browser_download_url;
+fn extract_submission(
+    mut rel: GHRelPayload,
+    secret: String,
+    manifest: Option<Manifest>,
+) -> Result<Submission, &'static str> {
+    const BAD_FORMAT: &str = "Bad format. Did you have a release asset?";
+
+    let (owner, name) = rel.repository.full_name.split_once('/').ok_or(BAD_FORMAT)?;
+    let manifest = manifest.unwrap_or_default();
+
+    let icon = match &manifest.icon {
+        Some(icon) => crate::manifest::icon_url(&rel, icon)?,
+        None => format!(
+            "https://raw.githubusercontent.com/{}/{}/icon.png",
+            rel.repository.full_name, rel.release.tag_name
+        ),
+    };
+
+    // Every asset is installed, unless the manifest picks which
+    let assets = &mut rel.release.assets;
+    assets.retain(|a| !crate::manifest::is_manifest(&a.name));
+    if let Some(files) = &manifest.files {
+        if files
+            .keys()
+            .any(|name| !assets.iter().any(|a| &a.name == name))
+        {
+            return Err("Every file in the manifest must be one of the release's assets.");
+        }
+        assets.retain(|a| files.contains_key(&a.name));
+    }
+
+    let binary = match &manifest.binary {
+        Some(binary) => assets
+            .iter()
+            .find(|a| &a.name == binary)
+            .ok_or("The manifest's binary must be one of the release's assets, and one of its files if it lists them.")?,
+        None => assets.last().ok_or(BAD_FORMAT)?,
+    }
+    .browser_download_url
+    .clone();
+
+    let files = assets
+        .iter()
+        .map(|asset| {
+            let file = manifest.files.as_ref().and_then(|f| f.get(&asset.name));
+            SubmittedFile {
+                name: asset.name.clone(),
+                url: asset.browser_download_url.clone(),
+                role: file.and_then(|f| f.role),
+                path: file.and_then(|f| f.path.clone()),
+            }
+        })
+        .collect();
 
     let mut homepage = rel.repository.homepage.unwrap_or_default();
     if homepage.is_empty() {
         homepage = format!("https://github.com/{}#readme", rel.repository.full_name);
     }
 
-    Some(Submission {
+    Ok(Submission {
         name: name.to_owned(),
         owner: owner.to_owned(),
         secret,
-        description: rel.repository.description.unwrap_or_default(),
+        description: manifest
+            .description
+            .or(rel.repository.description)
+            .unwrap_or_default(),
         homepage,
-        icon: format!(
-            "https://raw.githubusercontent.com/{}/{}/icon.png",
-            rel.repository.full_name, rel.release.tag_name
-        ),
+        icon,
         version: rel.release.tag_name,
         binary,
+        dependencies: manifest.dependencies.unwrap_or_default(),
+        tags: manifest.tags.unwrap_or_default(),
+        game_versions: manifest.game_versions,
+        loader_version: manifest.loader_version,
+        files,
     })
 }
@@ -1,2 +1,49 @@
+use crate::prelude::*;
+use rocket::{http::Status, response::status::Custom};
+use std::net::IpAddr;
+
 pub(crate) mod submit;
 pub(crate) mod hook;
+pub(crate) mod secret;
+pub(crate) mod visibility;
+
+/// Finds a mod that hasn't been deleted and checks its secret.
+/// Wrong secrets are recorded in the audit log under `source`.
+pub(crate) async fn authorize(
+    id: &str,
+    secret: String,
+    source: &str,
+    ip: Option<IpAddr>,
+    mods: &Mods,
+) -> Result<ModEntry, Custom<&'static str>> {
+    let entry = match mods.get(id).await {
+        Ok(Some(e)) if e.deleted.is_none() => e,
+        Ok(_) => return Err(Custom(Status::NotFound, "The mod doesn't exist.")),
+        Err(e) => {
+            dbg!(e);
+            return Err(server_err("The server encountered an error."));
+        }
+    };
+
+    if crate::secrets::verify(secret, entry.secret.clone()).await {
+        Ok(entry)
+    } else {
+        AuditEvent::new(id, source, "wrong-secret", ip)
+            .record(mods)
+            .await;
+        Err(client_err("The secret is incorrect."))
+    }
+}
+
+/// Records the outcome of a mutation that doesn't involve a new release.
+pub(crate) async fn audit_result(
+    id: &str,
+    source: &str,
+    ip: Option<IpAddr>,
+    result: SimpleResponse,
+    mods: &Mods,
+) -> SimpleResponse {
+    let outcome = if result.is_ok() { "success" } else { "error" };
+    AuditEvent::new(id, source, outcome, ip).record(mods).await;
+    result
+}
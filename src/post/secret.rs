@@ -0,0 +1,44 @@
+use crate::prelude::*;
+use rocket::{post, serde::json::Json, State};
+use std::net::IpAddr;
+
+#[post("/<owner>/<name>/secret", data = "<data>", format = "application/json")]
+pub(crate) async fn rotate_secret(
+    owner: &str,
+    name: &str,
+    data: Json<SecretChange>,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    keys: &State<HookKeys>,
+    _t: Throttle,
+) -> SimpleResponse {
+    if let Some(e) = secret_errors(&data.new_secret) {
+        return Err(client_err(e));
+    }
+
+    let data = data.0;
+    let id = format!("{owner}/{name}");
+    let entry = super::authorize(&id, data.secret, "secret", ip, mods).await?;
+
+    let secret = crate::secrets::hash(data.new_secret.clone()).await;
+    let hook_key = keys.seal(&data.new_secret);
+
+    // Matching on the old hash stops two concurrent rotations from both succeeding
+    let result = match mods
+        .set_secret(&entry.id, Some(&entry.secret), &secret, &hook_key)
+        .await
+    {
+        Ok(true) => Ok("Successfully changed secret."),
+        Ok(false) => Err(client_err(
+            "The secret changed while processing the request.",
+        )),
+        Err(e) => {
+            dbg!(e);
+            Err(server_err(
+                "Failed to change secret because of an internal error.",
+            ))
+        }
+    };
+
+    super::audit_result(&id, "secret", ip, result, mods).await
+}
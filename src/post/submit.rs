@@ -1,47 +1,317 @@
 use crate::prelude::*;
-use rocket::{post, serde::json::Json, State};
+use rocket::{http::Status, post, response::status::Custom, serde::json::Json, State};
+use std::net::IpAddr;
 
 #[post("/", data = "<data>", format = "application/json")]
-pub(crate) async fn submit(data: Json<Submission>, mods: &State<Mods>) -> SimpleResponse {
-    let entry = ModEntry::from_submission(data.0).map_err(client_err)?;
+pub(crate) async fn submit(
+    data: Json<Submission>,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    keys: &State<HookKeys>,
+    providers: &State<Providers>,
+    fetch: &State<Fetch>,
+    _t: Throttle,
+) -> SimpleResponse {
+    accept("submit", data.0, ip, mods, keys, providers, fetch).await
+}
+
+/// Validates a submission, then inserts it if its secret and version check out.
+/// Every attempt is recorded in the audit log under `source`.
+pub(crate) async fn accept(
+    source: &str,
+    submission: Submission,
+    ip: Option<IpAddr>,
+    mods: &Mods,
+    keys: &HookKeys,
+    providers: &Providers,
+    fetch: &Fetch,
+) -> SimpleResponse {
+    let checked = check(source, submission, ip, mods, providers).await?;
+    store(checked, mods, keys, fetch).await
+}
+
+/// A submission whose fields, secret and version checked out, along with its audit event.
+/// Its files are only fetched once it's stored.
+pub(crate) struct Checked {
+    entry: ModEntry,
+    event: AuditEvent,
+}
+
+/// Validates a submission and checks its secret and version against the stored mod.
+/// Rejections are recorded in the audit log under `source`.
+pub(crate) async fn check(
+    source: &str,
+    submission: Submission,
+    ip: Option<IpAddr>,
+    mods: &Mods,
+    providers: &Providers,
+) -> Result<Checked, Custom<&'static str>> {
+    let mut event = AuditEvent::new(
+        &format!("{}/{}", submission.owner.trim(), submission.name.trim()),
+        source,
+        "invalid",
+        ip,
+    );
+    event.new_version = Some(submission.version.clone());
+    event.new_binary = Some(submission.binary.clone());
+
+    let entry = match ModEntry::from_submission(submission, mods, providers).await {
+        Ok(entry) => entry,
+        Err(e) => {
+            event.record(mods).await;
+            return Err(client_err(e));
+        }
+    };
+
+    let verification = entry.verify(mods).await;
+
+    event.mod_id = entry.id.clone();
+    event.outcome = verification.outcome().to_owned();
+    event.new_version = Some(entry.info.version.clone());
+    event.new_binary = Some(entry.info.binary.clone());
+    if let Some(old) = verification.existing() {
+        event.old_version = Some(old.info.version.clone());
+        event.old_binary = Some(old.info.binary.clone());
+    }
 
-    match entry.verify(mods).await {
-        Success | NotFound => insert_mod(&entry, mods).await,
-        Old => Err(client_err("The version is outdated.")),
-        Failure => Err(client_err("The secret is incorrect.")),
+    let e = match verification {
+        // Only an admin can bring back a mod they took down
+        Success(old) if old.removed.is_some() => {
+            event.outcome = "removed".to_owned();
+            Custom(Status::Forbidden, "The mod was taken down by a moderator.")
+        }
+        Success(_) | NotFound => return Ok(Checked { entry, event }),
+        Old(_) => client_err("The version is outdated."),
+        Failure(_) => client_err("The secret is incorrect."),
         Error(e) => {
             dbg!(e);
-            Err(server_err("The server encountered an error."))
+            server_err("The server encountered an error.")
+        }
+    };
+    event.record(mods).await;
+    Err(e)
+}
+
+/// Fetches a checked submission's files, then inserts it. The outcome is audited.
+pub(crate) async fn store(
+    checked: Checked,
+    mods: &Mods,
+    keys: &HookKeys,
+    fetch: &Fetch,
+) -> SimpleResponse {
+    let Checked {
+        mut entry,
+        mut event,
+    } = checked;
+
+    // Only fetched once the submission is otherwise acceptable
+    let result = match fetch_files(&mut entry, fetch).await {
+        Ok(icon) => insert_mod(entry, icon, mods, keys).await,
+        Err((outcome, e)) => {
+            event.outcome = outcome.to_owned();
+            Err(client_err(e))
+        }
+    };
+
+    match result {
+        Ok(Upsert::Inserted) => event.outcome = "inserted".to_owned(),
+        Ok(Upsert::Updated) => event.outcome = "updated".to_owned(),
+        Ok(Upsert::Outdated) => event.outcome = "outdated".to_owned(),
+        Ok(Upsert::Removed) => event.outcome = "removed".to_owned(),
+        Err(_) if matches!(event.outcome.as_str(), "updated" | "inserted") => {
+            event.outcome = "error".to_owned()
         }
+        Err(_) => {}
+    }
+    event.record(mods).await;
+
+    match result? {
+        Upsert::Inserted => Ok("Successfully inserted mod."),
+        Upsert::Updated => Ok("Successfully updated mod."),
+        // Another release was accepted while this one was being verified
+        Upsert::Outdated => Err(client_err("The version is outdated.")),
+        // A moderator took it down while this release was being verified
+        Upsert::Removed => Err(Custom(
+            Status::Forbidden,
+            "The mod was taken down by a moderator.",
+        )),
     }
 }
 
-async fn insert_mod(entry: &ModEntry, mods: &Mods) -> SimpleResponse {
-    let query = doc! { "_id": &entry.id };
-    let update = doc! {
-        "$setOnInsert": {
-            "_id": &entry.id,
-            "downloads": 0u32,
-            "secret": &entry.secret,
-            "search": &entry.search,
-            "published": entry.published
-        },
-        "$set": {
-            "updated": entry.updated,
-            "info": to_bson(&entry.info).expect("Failed to deser ModInfo")
+/// Validates a prerelease from the webhook, and checks that its secret is right and that it's
+/// newer than both the current release and prerelease. Rejections are audited.
+pub(crate) async fn check_prerelease(
+    submission: Submission,
+    ip: Option<IpAddr>,
+    mods: &Mods,
+    providers: &Providers,
+) -> Result<Checked, Custom<&'static str>> {
+    let mut event = AuditEvent::new(
+        &format!("{}/{}", submission.owner.trim(), submission.name.trim()),
+        "hook",
+        "invalid",
+        ip,
+    );
+    event.new_version = Some(submission.version.clone());
+    event.new_binary = Some(submission.binary.clone());
+
+    let entry = match ModEntry::from_submission(submission, mods, providers).await {
+        Ok(entry) => entry,
+        Err(e) => {
+            event.record(mods).await;
+            return Err(client_err(e));
         }
     };
 
-    match mods.update_one(query, update, upsert()).await {
-        Ok(r) => match r.upserted_id.is_some() {
-            true => Ok("Successfully inserted mod."),
-            false => Ok("Successfully updated mod."),
-        },
+    let verification = entry.verify(mods).await;
+    event.mod_id = entry.id.clone();
+    if let Some(old) = verification.existing() {
+        event.old_version = Some(old.info.version.clone());
+        event.old_binary = Some(old.info.binary.clone());
+    }
+
+    match check_newer(&entry, verification) {
+        Ok(()) => Ok(Checked { entry, event }),
+        Err((outcome, e)) => {
+            event.outcome = outcome.to_owned();
+            event.record(mods).await;
+            Err(e)
+        }
+    }
+}
+
+// Checks a prerelease against the mod's releases, or returns the audit outcome and error
+fn check_newer(
+    entry: &ModEntry,
+    verification: Verification,
+) -> Result<(), (&'static str, Custom<&'static str>)> {
+    let old = match verification {
+        Success(old) if old.removed.is_some() => {
+            return Err((
+                "removed",
+                Custom(Status::Forbidden, "The mod was taken down by a moderator."),
+            ))
+        }
+        Success(old) if old.deleted.is_none() => old,
+        Success(_) | NotFound => {
+            return Err((
+                "invalid",
+                client_err("A mod's first release can't be a prerelease."),
+            ))
+        }
+        Old(_) => return Err(("outdated", client_err("The version is outdated."))),
+        Failure(_) => return Err(("wrong-secret", client_err("The secret is incorrect."))),
+        Error(e) => {
+            dbg!(e);
+            return Err(("error", server_err("The server encountered an error.")));
+        }
+    };
+
+    match old.current_prerelease() {
+        Some(prerelease) if !is_newer(&entry.info.version, &prerelease.info.version) => {
+            Err(("outdated", client_err("The version is outdated.")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fetches a checked prerelease's files, then stores it as the mod's prerelease. The outcome is
+/// audited.
+pub(crate) async fn store_prerelease(
+    checked: Checked,
+    mods: &Mods,
+    fetch: &Fetch,
+) -> SimpleResponse {
+    let Checked { entry, mut event } = checked;
+
+    let result = set_prerelease(entry, mods, fetch).await;
+    event.outcome = match &result {
+        Ok(()) => "prerelease".to_owned(),
+        Err((outcome, _)) => outcome.to_string(),
+    };
+    event.record(mods).await;
+
+    match result {
+        Ok(()) => Ok("Successfully updated mod's prerelease."),
+        Err((_, e)) => Err(e),
+    }
+}
+
+// Stores a checked prerelease, or returns the audit outcome and error
+async fn set_prerelease(
+    mut entry: ModEntry,
+    mods: &Mods,
+    fetch: &Fetch,
+) -> Result<(), (&'static str, Custom<&'static str>)> {
+    // The icon is checked, but only the current release's icon is mirrored
+    fetch_files(&mut entry, fetch)
+        .await
+        .map_err(|(outcome, e)| (outcome, client_err(e)))?;
+
+    let release = Release {
+        published: entry.published,
+        info: entry.info,
+    };
+    match mods.set_prerelease(&entry.id, Some(&release)).await {
+        Ok(_) => Ok(()),
         Err(e) => {
             dbg!(e);
-            Err(server_err(
-                "Failed to upsert mod because of an internal error.",
+            Err((
+                "error",
+                server_err("Failed to store prerelease because of an internal error."),
             ))
         }
     }
 }
+
+// Records each file's hash and returns the normalised icon, or the audit outcome and error
+async fn fetch_files(
+    entry: &mut ModEntry,
+    fetch: &Fetch,
+) -> Result<Vec<u8>, (&'static str, &'static str)> {
+    let info = &mut entry.info;
+    for file in &mut info.files {
+        let (sha256, size) = if file.url == info.binary {
+            let hash = fetch
+                .binary(&file.url)
+                .await
+                .map_err(|e| ("bad-binary", e))?;
+            info.sha256 = Some(hash.0.clone());
+            info.size = Some(hash.1);
+            hash
+        } else {
+            fetch.file(&file.url).await.map_err(|e| ("bad-file", e))?
+        };
+        file.sha256 = Some(sha256);
+        file.size = Some(size);
+    }
+
+    fetch
+        .icon(&entry.info.icon)
+        .await
+        .map_err(|e| ("bad-icon", e))
+}
+
+async fn insert_mod(
+    mut entry: ModEntry,
+    icon: Vec<u8>,
+    mods: &Mods,
+    keys: &HookKeys,
+) -> Result<Upsert, Custom<&'static str>> {
+    // Only used if the entry is new; existing entries keep their secret
+    entry.hook_key = Some(keys.seal(&entry.secret));
+    entry.secret = crate::secrets::hash(entry.secret).await;
+
+    let upsert = mods.upsert_if_newer(&entry).await.map_err(|e| {
+        dbg!(e);
+        server_err("Failed to upsert mod because of an internal error.")
+    })?;
+
+    // Without a mirrored icon, the icon route falls back to the icon's URL
+    if matches!(upsert, Upsert::Inserted | Upsert::Updated) {
+        if let Err(e) = mods.set_icon(&entry.id, &icon).await {
+            println!("Error while storing icon of {}: {e}", &entry.id);
+        }
+    }
+    Ok(upsert)
+}
@@ -0,0 +1,34 @@
+use crate::prelude::*;
+use rocket::{post, serde::json::Json, State};
+use std::net::IpAddr;
+
+#[post(
+    "/<owner>/<name>/visibility",
+    data = "<data>",
+    format = "application/json"
+)]
+pub(crate) async fn visibility(
+    owner: &str,
+    name: &str,
+    data: Json<VisibilityChange>,
+    ip: Option<IpAddr>,
+    mods: &State<Mods>,
+    _t: Throttle,
+) -> SimpleResponse {
+    let data = data.0;
+    let id = format!("{owner}/{name}");
+    let entry = super::authorize(&id, data.secret, "visibility", ip, mods).await?;
+
+    let result = match mods.set_unlisted(&entry.id, data.unlisted).await {
+        Ok(_) if data.unlisted => Ok("Successfully unlisted mod."),
+        Ok(_) => Ok("Successfully listed mod."),
+        Err(e) => {
+            dbg!(e);
+            Err(server_err(
+                "Failed to change visibility because of an internal error.",
+            ))
+        }
+    };
+
+    super::audit_result(&id, "visibility", ip, result, mods).await
+}
@@ -1,12 +1,15 @@
-use mongodb::options::UpdateOptions;
 use rocket::{http::Status, response::status::Custom};
 
+pub(crate) use crate::config::Config;
+pub(crate) use crate::fetch::Fetch;
 pub use crate::guards::*;
+pub(crate) use crate::providers::Providers;
+pub(crate) use crate::secrets::HookKeys;
 pub use crate::serde::Verification::*;
 pub use crate::serde::*;
-pub use mongodb::bson::{doc, to_bson};
+pub(crate) use crate::store::{ListQuery, ModStore, Sort, StoreError, Upsert};
 
-pub(crate) type Mods = mongodb::Collection<crate::serde::ModEntry>;
+pub(crate) type Mods = std::sync::Arc<dyn ModStore>;
 pub(crate) type SimpleResponse = Result<&'static str, Custom<&'static str>>;
 
 pub(crate) fn client_err<R>(message: R) -> Custom<R> {
@@ -17,11 +20,6 @@ pub(crate) fn server_err<R>(message: R) -> Custom<R> {
     Custom(Status::InternalServerError, message)
 }
 
-// Shorthand for 'upsert' options
-pub(crate) fn upsert() -> UpdateOptions {
-    UpdateOptions::builder().upsert(true).build()
-}
-
 // Shorthand for short-circuiting bad results
 macro_rules! sc {
     ($e:expr, $err:expr) => {{
@@ -0,0 +1,148 @@
+use regex::Regex;
+use rocket::serde::{Deserialize, Serialize};
+
+/// A place binaries can be hosted. A binary URL is accepted if a provider's pattern matches it.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Provider {
+    // Shown to submitters when no provider matches
+    pub name: String,
+    // Matched against the start of the URL
+    pub pattern: String,
+    // Rewrites the matched part of the URL, with `$1` for the first capture group and so on.
+    // Without a rewrite, the matched part is kept as is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewrite: Option<String>,
+}
+
+impl Provider {
+    fn new(name: &str, pattern: &str, rewrite: Option<&str>) -> Self {
+        Self {
+            name: name.to_owned(),
+            pattern: pattern.to_owned(),
+            rewrite: rewrite.map(str::to_owned),
+        }
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Provider::new(
+                "Google Drive",
+                r"https://drive\.google\.com/file/d/([^/?#]+)",
+                Some("https://drive.google.com/uc?export=download&id=$1"),
+            ),
+            Provider::new(
+                "Google Drive",
+                r"https://drive\.google\.com/uc\?export=download&id=[^/&#]+",
+                None,
+            ),
+            Provider::new(
+                "GitHub",
+                r"https://github\.com/[^/?#]+/[^/?#]+/releases/download/[^/?#]+/[^/?#]+",
+                None,
+            ),
+            Provider::new(
+                "GitLab",
+                r"https://gitlab\.com/(?:[^/?#]+/)+(?:-/releases/[^/?#]+/downloads/[^?#]+|uploads/[0-9a-f]{32}/[^/?#]+)",
+                None,
+            ),
+            Provider::new(
+                "Codeberg",
+                r"https://codeberg\.org/(?:[^/?#]+/[^/?#]+/releases/download/[^/?#]+/[^/?#]+|attachments/[0-9a-f-]{36})",
+                None,
+            ),
+            Provider::new(
+                "Discord",
+                r"https://cdn\.discordapp\.com/attachments/\d+/\d+/[^/?#]+",
+                None,
+            ),
+        ]
+    }
+}
+
+/// The configured providers, with their patterns compiled.
+pub(crate) struct Providers {
+    providers: Vec<(Regex, Provider)>,
+    error: &'static str,
+}
+
+impl Providers {
+    pub fn new(providers: &[Provider]) -> Result<Self, String> {
+        if providers.is_empty() {
+            return Err("`binary_providers` must list at least one provider.".to_owned());
+        }
+
+        let mut compiled = Vec::with_capacity(providers.len());
+        let mut names: Vec<&str> = Vec::new();
+
+        for provider in providers {
+            // Anchored so a URL can't smuggle an accepted one into its query string
+            let regex = Regex::new(&format!("^(?:{})", provider.pattern))
+                .map_err(|e| format!("Provider `{}` has an invalid pattern: {e}", provider.name))?;
+
+            if !names.contains(&provider.name.as_str()) {
+                names.push(&provider.name);
+            }
+            compiled.push((regex, provider.clone()));
+        }
+
+        // Built once at launch, so it can be handed out like the other error messages
+        let error = format!(
+            "Each binary URL must be a file from one of these providers: {}.",
+            names.join(", ")
+        );
+
+        Ok(Self {
+            providers: compiled,
+            error: Box::leak(error.into_boxed_str()),
+        })
+    }
+
+    /// Normalises a binary URL using the first provider that matches it.
+    pub fn process(&self, url: &str) -> Result<String, &'static str> {
+        for (regex, provider) in &self.providers {
+            if let Some(caps) = regex.captures(url) {
+                let mut normalised = String::new();
+                match &provider.rewrite {
+                    Some(rewrite) => caps.expand(rewrite, &mut normalised),
+                    None => normalised.push_str(&caps[0]),
+                }
+                return Ok(normalised);
+            }
+        }
+        Err(self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors_patterns() {
+        let providers = Providers::new(&Provider::defaults()).unwrap();
+        let github = "https://github.com/a/b/releases/download/1.0.0/B.dll";
+
+        assert_eq!(providers.process(github), Ok(github.to_owned()));
+        assert!(providers
+            .process(&format!("https://evil.com/?{github}"))
+            .is_err());
+        assert!(providers.process(&format!(" {github}")).is_err());
+    }
+
+    #[test]
+    fn rewrites_matches() {
+        let providers = Providers::new(&Provider::defaults()).unwrap();
+
+        assert_eq!(
+            providers.process("https://drive.google.com/file/d/abc123/view?usp=sharing"),
+            Ok("https://drive.google.com/uc?export=download&id=abc123".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_config() {
+        assert!(Providers::new(&[]).is_err());
+        assert!(Providers::new(&[Provider::new("Bad", "(", None)]).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{prelude::*, serde::ModInfo};
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The lowest score an entry can have and still match a search. A description word scores this
+/// much on its own, and a misspelt name needs a few trigrams in common.
+pub(crate) const MIN_SCORE: u32 = 3;
+
+// How much a word is worth in each part of an entry
+const NAME: u32 = 10;
+const OWNER: u32 = 6;
+const TAG: u32 = 6;
+const DESCRIPTION: u32 = 3;
+// Word prefixes let searches match while they're being typed
+const PREFIX_DIVISOR: u32 = 2;
+const MIN_PREFIX: usize = 3;
+// Trigrams let misspelt words match
+const TRIGRAM: u32 = 1;
+
+/// A word an entry can be found by, and how much matching it is worth.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Term {
+    pub term: String,
+    pub weight: u32,
+}
+
+/// Everything an entry can be searched by. Each term keeps the highest weight it's found with.
+pub(crate) fn terms(id: &str, info: &ModInfo) -> Vec<Term> {
+    let (owner, name) = id.split_once('/').unwrap_or(("", id));
+    let mut terms = BTreeMap::new();
+
+    let tags = info.tags.iter().map(|t| (t.as_str(), TAG));
+    for (text, weight) in [(name, NAME), (owner, OWNER)].into_iter().chain(tags) {
+        for word in words(text) {
+            add(&mut terms, &word, weight);
+
+            let chars: Vec<char> = word.chars().collect();
+            for len in MIN_PREFIX..chars.len() {
+                let prefix: String = chars[..len].iter().collect();
+                add(&mut terms, &prefix, weight / PREFIX_DIVISOR);
+            }
+            for trigram in trigrams(&word) {
+                add(&mut terms, &trigram, TRIGRAM);
+            }
+        }
+    }
+    for word in words(&info.description) {
+        add(&mut terms, &word, DESCRIPTION);
+    }
+
+    terms
+        .into_iter()
+        .map(|(term, weight)| Term { term, weight })
+        .collect()
+}
+
+/// The terms a search looks for: each of its words, and their trigrams. Sorted, so they can be
+/// passed to [`score`].
+pub(crate) fn query_terms(search: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in words(search) {
+        terms.extend(trigrams(&word));
+        terms.push(word);
+    }
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// The sum of the weights of an entry's terms that the search looks for.
+pub(crate) fn score(terms: &[Term], query: &[String]) -> u32 {
+    terms
+        .iter()
+        .filter(|t| query.binary_search(&t.term).is_ok())
+        .map(|t| t.weight)
+        .sum()
+}
+
+/// Recomputes the search fields of entries indexed by an older version, or before there was
+/// anything but the ID to search by.
+pub(crate) async fn migrate(mods: &Mods) {
+    let entries = mods.all().await.expect("failed to find search fields");
+    let mut count = 0;
+
+    for mut entry in entries {
+        let (search, terms) = (entry.search.clone(), entry.terms.clone());
+        entry.reindex();
+
+        if entry.search != search || entry.terms != terms {
+            mods.import(&entry)
+                .await
+                .expect("failed to recompute search fields");
+            count += 1;
+        }
+    }
+
+    if count > 0 {
+        println!("Reindexed {count} mods for search");
+    }
+}
+
+fn add(terms: &mut BTreeMap<String, u32>, term: &str, weight: u32) {
+    let old = terms.entry(term.to_owned()).or_insert(weight);
+    *old = weight.max(*old);
+}
+
+// Lowercase words, with camelCase and digits split off. `CentiShields2` is `centi`, `shields`,
+// `2`, and `centishields2` so it can still be searched for in one piece.
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+
+    for run in text.split(|c: char| !c.is_alphanumeric()) {
+        let parts = camel_case(run);
+        if parts.len() > 1 {
+            words.push(run.to_lowercase());
+        }
+        words.extend(parts.into_iter().map(|p| p.to_lowercase()));
+    }
+    words
+}
+
+fn camel_case(run: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = run.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for i in 1..chars.len() {
+        let (prev, (at, cur)) = (chars[i - 1].1, chars[i]);
+        let next = chars.get(i + 1).map(|c| c.1);
+
+        // `aB`, the `S` in `HTTPServer`, and either side of a number
+        let boundary = (prev.is_lowercase() && cur.is_uppercase())
+            || (prev.is_uppercase() && cur.is_uppercase() && next.is_some_and(char::is_lowercase))
+            || (prev.is_numeric() != cur.is_numeric());
+
+        if boundary {
+            parts.push(&run[start..at]);
+            start = at;
+        }
+    }
+    if start < run.len() {
+        parts.push(&run[start..]);
+    }
+    parts
+}
+
+// Padded so the start and end of a word count for more. Marked with `#` so they can't be
+// mistaken for words.
+fn trigrams(word: &str) -> Vec<String> {
+    let padded: Vec<char> = format!(" {word} ").chars().collect();
+    padded
+        .windows(3)
+        .map(|w| format!("#{}", w.iter().collect::<String>()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_camel_case() {
+        assert_eq!(camel_case("CentiShields"), ["Centi", "Shields"]);
+        assert_eq!(camel_case("HTTPServer"), ["HTTP", "Server"]);
+        assert_eq!(camel_case("lib2go"), ["lib", "2", "go"]);
+        assert_eq!(camel_case("plain"), ["plain"]);
+    }
+
+    #[test]
+    fn splits_words() {
+        assert_eq!(words("centipede-shields"), ["centipede", "shields"]);
+        assert_eq!(
+            words("CentiShields2"),
+            ["centishields2", "centi", "shields", "2"]
+        );
+        assert!(words("  --  ").is_empty());
+    }
+
+    #[test]
+    fn tolerates_typos() {
+        let info = ModInfo {
+            binary: String::new(),
+            version: "1.0.0".to_owned(),
+            description: String::new(),
+            homepage: String::new(),
+            icon: String::new(),
+            dependencies: Default::default(),
+            tags: Vec::new(),
+            game_versions: None,
+            loader_version: None,
+            sha256: None,
+            size: None,
+            files: Vec::new(),
+        };
+        let terms = terms("Dual-Iron/centipede-shields", &info);
+
+        assert!(score(&terms, &query_terms("sheilds")) >= MIN_SCORE);
+        assert!(score(&terms, &query_terms("centi")) >= MIN_SCORE);
+        assert!(score(&terms, &query_terms("lizard")) < MIN_SCORE);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::prelude::*;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use sha2::{Digest, Sha256};
+
+/// Hashes a mod secret for storage.
+pub(crate) async fn hash(secret: String) -> String {
+    rocket::tokio::task::spawn_blocking(move || hash_blocking(&secret))
+        .await
+        .expect("secret hashing panicked")
+}
+
+/// Checks a mod secret against its stored hash in constant time.
+pub(crate) async fn verify(secret: String, hash: String) -> bool {
+    rocket::tokio::task::spawn_blocking(move || match PasswordHash::new(&hash) {
+        Ok(h) => Argon2::default()
+            .verify_password(secret.as_bytes(), &h)
+            .is_ok(),
+        Err(_) => false,
+    })
+    .await
+    .expect("secret verification panicked")
+}
+
+fn hash_blocking(secret: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(secret.as_bytes(), &salt)
+        .expect("failed to hash secret")
+        .to_string()
+}
+
+fn is_hashed(secret: &str) -> bool {
+    secret.starts_with("$argon2")
+}
+
+/// Encrypts the copy of each secret that GitHub webhook signatures are checked against.
+/// Signatures can only be checked with the secret itself, so a hash won't do.
+#[derive(Clone)]
+pub(crate) struct HookKeys(ChaCha20Poly1305);
+
+impl HookKeys {
+    /// Derives the encryption key from an arbitrary server-side string.
+    pub fn new(key: &str) -> Self {
+        let key = Sha256::digest(key.as_bytes());
+        Self(ChaCha20Poly1305::new(&key))
+    }
+
+    pub fn seal(&self, secret: &str) -> String {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            self.0
+                .encrypt(&nonce, secret.as_bytes())
+                .expect("failed to encrypt secret"),
+        );
+        hex::encode(sealed)
+    }
+
+    pub fn open(&self, sealed: &str) -> Option<String> {
+        let sealed = hex::decode(sealed).ok()?;
+        if sealed.len() < 12 {
+            return None;
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(12);
+        let secret = self.0.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+        String::from_utf8(secret).ok()
+    }
+}
+
+/// Replaces every plaintext secret left in the store with a hash.
+pub(crate) async fn migrate(mods: &Mods, keys: &HookKeys) {
+    let entries = mods.all().await.expect("failed to find secrets");
+    let mut count = 0;
+
+    for entry in entries.iter().filter(|e| !is_hashed(&e.secret)) {
+        let secret = hash(entry.secret.clone()).await;
+        let hook_key = keys.seal(&entry.secret);
+
+        // Matching on the old secret leaves entries alone if they changed in the meantime
+        mods.set_secret(&entry.id, Some(&entry.secret), &secret, &hook_key)
+            .await
+            .expect("failed to hash secret");
+        count += 1;
+    }
+
+    if count > 0 {
+        println!("Hashed {count} plaintext secrets");
+    }
+}
@@ -1,7 +1,10 @@
 use crate::prelude::*;
-use mongodb::bson::doc;
-use regex::Regex;
+use crate::{compat::VersionRange, search::Term};
 use rocket::serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+};
 
 // GitHub webhook support
 #[derive(Deserialize)]
@@ -26,11 +29,17 @@ pub(crate) struct GHRepo {
 #[derive(Deserialize)]
 pub(crate) struct GHRelease {
     pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    pub body: Option<String>,
     pub assets: Vec<GHAsset>,
 }
 
 #[derive(Deserialize)]
 pub(crate) struct GHAsset {
+    pub name: String,
     pub browser_download_url: String,
 }
 
@@ -45,42 +54,259 @@ pub(crate) struct Submission {
     pub version: String,
     pub icon: String,
     pub binary: String,
+
+    // Maps `owner/name` to a semver version requirement
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+
+    // Each tag is from `tags::TAGS`
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    // Semver requirements on the game and mod loader versions the release works with
+    #[serde(default)]
+    pub game_versions: Option<String>,
+    #[serde(default)]
+    pub loader_version: Option<String>,
+
+    // Every file the release installs, including the binary. Just the binary if empty.
+    #[serde(default)]
+    pub files: Vec<SubmittedFile>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SubmittedFile {
+    pub name: String,
+    pub url: String,
+    // Guessed from the file's name if missing
+    #[serde(default)]
+    pub role: Option<FileRole>,
+    // Defaults to the role's folder
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+// What authors can say about a release in an `rdb.json` or `rdb.toml` manifest. Anything left
+// out is worked out from the release like before.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Manifest {
+    // The name of the release asset to use as the binary
+    pub binary: Option<String>,
+    // A path in the repository at the release's tag, or a URL
+    pub icon: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub dependencies: Option<BTreeMap<String, String>>,
+    pub game_versions: Option<String>,
+    pub loader_version: Option<String>,
+    // Maps release asset names to how they're installed. Assets left out aren't installed.
+    pub files: Option<BTreeMap<String, ManifestFile>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ManifestFile {
+    pub role: Option<FileRole>,
+    pub path: Option<String>,
+}
+
+// Secret rotation
+#[derive(Deserialize)]
+pub(crate) struct SecretChange {
+    pub secret: String,
+    pub new_secret: String,
+}
+
+// Deletion
+#[derive(Deserialize)]
+pub(crate) struct Credentials {
+    pub secret: String,
+}
+
+// Visibility changes
+#[derive(Deserialize)]
+pub(crate) struct VisibilityChange {
+    pub secret: String,
+    pub unlisted: bool,
+}
+
+// Admin requests
+#[derive(Deserialize)]
+pub(crate) struct AdminVisibility {
+    pub unlisted: bool,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AdminSecretReset {
+    pub new_secret: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct OwnershipTransfer {
+    pub new_owner: String,
 }
 
 // Final mod entry
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct ModEntry {
     #[serde(rename = "_id")]
     pub id: String,
+    // Argon2 hash of the secret; plaintext until the secret has been hashed
     pub secret: String,
+    // Encrypted copy of the secret for checking webhook signatures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hook_key: Option<String>,
+    // Prefix n-grams of the ID
     pub search: String,
+    // Weighted words from the ID and info, for fuzzy searches. Computed by `search::terms`.
+    #[serde(default)]
+    pub terms: Vec<Term>,
     pub published: i64,
     pub info: ModInfo,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub downloads: Option<u32>,
     pub updated: i64,
+
+    // Every accepted release in the order it was submitted. Never modified after insertion.
+    #[serde(default)]
+    pub releases: Vec<Release>,
+
+    // Unlisted mods are left out of listings but can still be fetched directly
+    #[serde(default)]
+    pub unlisted: bool,
+
+    // Deleted mods are treated as missing, but keep their name reserved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<i64>,
+
+    // Taken down by a moderator. Removed mods are also deleted, and only an admin can restore them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<i64>,
+
+    // When the current binary and icon links were last checked, and whether either was dead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_checked: Option<i64>,
+    #[serde(default)]
+    pub broken: bool,
+
+    // Downloads within the trending window, as of the last time trends were updated
+    #[serde(default)]
+    pub trending: u32,
+
+    // The game versions the current release supports, if it says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_range: Option<VersionRange>,
+
+    // Whether the current release was unpublished or deleted on GitHub. Cleared by the next release.
+    #[serde(default)]
+    pub withdrawn: bool,
+
+    // The newest prerelease, which is only current while it's newer than the current release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prerelease: Option<Release>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Release {
+    pub published: i64,
+    pub info: ModInfo,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct ModInfo {
     pub binary: String,
     pub version: String,
     pub description: String,
     pub homepage: String,
     pub icon: String,
+
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_versions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loader_version: Option<String>,
+
+    // Hex SHA-256 and byte size of the binary when it was submitted.
+    // Releases from before binaries were fetched have neither.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+
+    // Releases from before files were recorded have none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<ModFile>,
+}
+
+// A file a release installs
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct ModFile {
+    pub name: String,
+    pub url: String,
+    pub role: FileRole,
+    // Where the file goes, relative to the game's folder
+    pub path: String,
+
+    // Hex SHA-256 and byte size of the file when it was submitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FileRole {
+    Plugin,
+    Patch,
+    Asset,
+}
+
+// Marks that an IP address downloaded a mod on a given day
+#[derive(Serialize, Deserialize)]
+pub(crate) struct DownloadRecord {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub created: mongodb::bson::DateTime,
+    pub mod_id: String,
+}
+
+// One mutation, or attempted mutation, of the mods collection
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct AuditEvent {
+    pub mod_id: String,
+    pub time: i64,
+    // The route that made the change, like `submit`, `hook`, or `admin-delete`
+    pub source: String,
+    pub outcome: String,
+    pub ip: Option<String>,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub old_binary: Option<String>,
+    pub new_binary: Option<String>,
 }
 
 pub enum Verification {
-    Success,
-    Failure,
+    Success(Box<ModEntry>),
+    Failure(Box<ModEntry>),
     NotFound,
-    Old,
-    Error(mongodb::error::Error),
+    Old(Box<ModEntry>),
+    Error(StoreError),
 }
 
 impl ModEntry {
-    pub fn from_submission(mut submission: Submission) -> Result<Self, &'static str> {
+    pub async fn from_submission(
+        mut submission: Submission,
+        mods: &Mods,
+        providers: &Providers,
+    ) -> Result<Self, &'static str> {
         fn trim_in_place(s: &mut String) {
             s.truncate(s.trim_end().len());
             s.drain(..(s.len() - s.trim_start().len()));
@@ -100,46 +326,120 @@ impl ModEntry {
         trim_in_place(&mut submission.version);
         trim_in_place(&mut submission.icon);
         trim_in_place(&mut submission.binary);
+        for file in &mut submission.files {
+            trim_in_place(&mut file.name);
+            trim_in_place(&mut file.url);
+            if let Some(path) = &mut file.path {
+                trim_in_place(path);
+            }
+        }
+        // Submissions that only name a binary install just that
+        if submission.files.is_empty() {
+            submission.files.push(SubmittedFile {
+                name: default_name(&submission.binary, &submission.name),
+                url: submission.binary.clone(),
+                role: Some(FileRole::Plugin),
+                path: None,
+            });
+        }
+        crate::tags::normalise(&mut submission.tags);
+        for req in [
+            &mut submission.game_versions,
+            &mut submission.loader_version,
+        ] {
+            if let Some(r) = req {
+                trim_in_place(r);
+            }
+            // Empty requirements are the same as none
+            if req.as_deref() == Some("") {
+                *req = None;
+            }
+        }
 
         if let Some(value) = errors(&submission) {
             return Err(value);
         }
 
-        match process_binary(&mut submission.binary) {
+        match providers.process(&submission.binary) {
             Ok(s) => submission.binary = s,
             Err(e) => return Err(e),
         }
+        for file in &mut submission.files {
+            file.url = providers.process(&file.url)?;
+        }
 
         let id = format!("{}/{}", submission.owner, submission.name);
 
+        if let Some(value) = dependency_errors(&id, &submission.dependencies, mods).await {
+            return Err(value);
+        }
+
+        let info = ModInfo {
+            binary: submission.binary,
+            version: submission.version,
+            description: submission.description,
+            homepage: submission.homepage,
+            icon: submission.icon,
+            dependencies: submission.dependencies,
+            tags: submission.tags,
+            game_versions: submission.game_versions,
+            loader_version: submission.loader_version,
+            sha256: None,
+            size: None,
+            files: submission.files.into_iter().map(ModFile::from).collect(),
+        };
+
         Ok(Self {
             secret: submission.secret,
+            hook_key: None,
             search: n_gram(&id, 2),
+            terms: crate::search::terms(&id, &info),
+            game_range: game_range(&info),
             published: time,
-            info: ModInfo {
-                binary: submission.binary,
-                version: submission.version,
-                description: submission.description,
-                homepage: submission.homepage,
-                icon: submission.icon,
-            },
+            info,
             downloads: None,
             updated: time,
+            releases: Vec::new(),
+            unlisted: false,
+            deleted: None,
+            removed: None,
+            last_checked: None,
+            broken: false,
+            trending: 0,
+            withdrawn: false,
+            prerelease: None,
             id,
         })
     }
 
-    pub async fn verify(&self, mods: &Mods) -> Verification {
-        let query = doc! { "_id": &self.id };
+    /// Recomputes the search fields after the ID or info changes.
+    pub fn reindex(&mut self) {
+        self.search = n_gram(&self.id, 2);
+        self.terms = crate::search::terms(&self.id, &self.info);
+    }
 
-        match mods.find_one(query, None).await {
+    /// The release history, oldest first. Entries published before release history was kept
+    /// only know about their current release.
+    pub fn history(&self) -> Vec<Release> {
+        if self.releases.is_empty() {
+            vec![Release {
+                published: self.updated,
+                info: self.info.clone(),
+            }]
+        } else {
+            self.releases.clone()
+        }
+    }
+
+    pub async fn verify(&self, mods: &Mods) -> Verification {
+        match mods.get(&self.id).await {
             Ok(Some(e)) => {
-                if e.secret != self.secret {
-                    Failure
+                if !crate::secrets::verify(self.secret.clone(), e.secret.clone()).await {
+                    Failure(Box::new(e))
                 } else if !self.newer(&e) {
-                    Old
+                    Old(Box::new(e))
                 } else {
-                    Success
+                    Success(Box::new(e))
                 }
             }
             Ok(None) => NotFound,
@@ -150,54 +450,135 @@ impl ModEntry {
         }
     }
 
-    fn newer(&self, other: &Self) -> bool {
-        use semver::Version;
+    pub fn newer(&self, other: &Self) -> bool {
+        is_newer(&self.info.version, &other.info.version)
+    }
+
+    /// The prerelease, if it's newer than the current release.
+    pub fn current_prerelease(&self) -> Option<&Release> {
+        self.prerelease
+            .as_ref()
+            .filter(|p| is_newer(&p.info.version, &self.info.version))
+    }
+}
+
+impl ModInfo {
+    /// The files the release installs. Releases from before files were recorded install just
+    /// their binary, which is named after the mod called `name` if its URL has no file name.
+    pub fn file_list(&self, name: &str) -> Vec<ModFile> {
+        if !self.files.is_empty() {
+            return self.files.clone();
+        }
+
+        let name = default_name(&self.binary, name);
+        vec![ModFile {
+            path: FileRole::Plugin.path(&name),
+            name,
+            url: self.binary.clone(),
+            role: FileRole::Plugin,
+            sha256: self.sha256.clone(),
+            size: self.size,
+        }]
+    }
+}
+
+impl From<SubmittedFile> for ModFile {
+    fn from(file: SubmittedFile) -> Self {
+        Self {
+            path: file_path(&file),
+            role: file_role(&file),
+            name: file.name,
+            url: file.url,
+            sha256: None,
+            size: None,
+        }
+    }
+}
 
-        match Version::parse(&self.info.version) {
-            Ok(v1) => match Version::parse(&other.info.version) {
-                Ok(v2) => v1 > v2,
-                Err(_) => false,
-            },
-            Err(_) => false,
+impl FileRole {
+    /// Guesses a file's role from its name. DLLs are assumed to be plugins.
+    pub fn guess(name: &str) -> Self {
+        if name.to_lowercase().ends_with(".dll") {
+            FileRole::Plugin
+        } else {
+            FileRole::Asset
+        }
+    }
+
+    /// Where a file with this role is installed by default, relative to the game's folder.
+    /// Assets go next to plugins, where plugins expect to find them.
+    pub fn path(self, name: &str) -> String {
+        match self {
+            FileRole::Patch => format!("BepInEx/patchers/{name}"),
+            FileRole::Plugin | FileRole::Asset => format!("BepInEx/plugins/{name}"),
         }
     }
 }
 
-fn process_binary(url: &str) -> Result<String, &'static str> {
-    lazy_static::lazy_static! {
-        // Capture 1 = google drive ID
-        static ref DRIVE: Regex = Regex::new(r#"https://drive.google.com/file/d/([^/]+)"#).unwrap();
-        static ref DRIVE_DIRECT: Regex = Regex::new(r#"https://drive.google.com/uc\?export=download&id=[^/]+"#).unwrap();
-        static ref GITHUB: Regex = Regex::new(r#"https://github.com/Dual-Iron/.+/.+/download/.+?/[^/]+"#).unwrap();
-        static ref DISCORD: Regex = Regex::new(r#"https://cdn.discordapp.com/attachments/\d+/\d+/[^/]+"#).unwrap();
+impl Verification {
+    /// The entry that was verified against, if there was one.
+    pub fn existing(&self) -> Option<&ModEntry> {
+        match self {
+            Success(e) | Failure(e) | Old(e) => Some(e),
+            NotFound | Error(_) => None,
+        }
     }
-    if let Some(drive) = DRIVE.captures(url) {
-        let id = &drive[1];
-        Ok(format!(
-            "https://drive.google.com/uc?export=download&id={id}"
-        ))
-    } else if let Some(other) = DRIVE_DIRECT.find(url) {
-        Ok(url[other.range()].to_string())
-    } else if let Some(other) = GITHUB.find(url) {
-        Ok(url[other.range()].to_string())
-    } else if let Some(other) = DISCORD.find(url) {
-        Ok(url[other.range()].to_string())
-    } else {
-        Err("Each binary URL must be a Google Drive file, GitHub release asset, or Discord attachment.")
+
+    pub fn outcome(&self) -> &'static str {
+        match self {
+            Success(_) => "updated",
+            Failure(_) => "wrong-secret",
+            NotFound => "inserted",
+            Old(_) => "outdated",
+            Error(_) => "error",
+        }
     }
 }
 
-fn errors(submission: &Submission) -> Option<&'static str> {
-    fn is_invalid(c: char) -> bool {
-        !c.is_alphanumeric() && !['.', '-', '_'].contains(&c)
+impl AuditEvent {
+    pub fn new(mod_id: &str, source: &str, outcome: &str, ip: Option<IpAddr>) -> Self {
+        Self {
+            mod_id: mod_id.to_owned(),
+            time: timestamp(),
+            source: source.to_owned(),
+            outcome: outcome.to_owned(),
+            ip: ip.map(|ip| ip.to_string()),
+            old_version: None,
+            new_version: None,
+            old_binary: None,
+            new_binary: None,
+        }
     }
 
+    /// Adds the event to the audit log. Failing to do so is logged but never fails the request.
+    pub async fn record(self, mods: &Mods) {
+        if let Err(e) = mods.record_audit(&self).await {
+            println!("Error while auditing mod {}: {e}", &self.mod_id);
+        }
+    }
+}
+
+/// Whether `version` is a newer semver version than `other`. False if either isn't semver.
+pub(crate) fn is_newer(version: &str, other: &str) -> bool {
+    use semver::Version;
+
+    match (Version::parse(version), Version::parse(other)) {
+        (Ok(v1), Ok(v2)) => v1 > v2,
+        _ => false,
+    }
+}
+
+fn is_invalid(c: char) -> bool {
+    !c.is_alphanumeric() && !['.', '-', '_'].contains(&c)
+}
+
+fn errors(submission: &Submission) -> Option<&'static str> {
     if submission.name.is_empty() || submission.name.len() > 39 {
         Some("Name must be 1-39 bytes.")
-    } else if submission.owner.is_empty() || submission.owner.len() > 39 {
-        Some("Owner must be 1-39 bytes.")
-    } else if submission.secret.is_empty() || submission.secret.len() > 500 {
-        Some("Secret must be 1-500 bytes.")
+    } else if let Some(e) = owner_errors(&submission.owner) {
+        Some(e)
+    } else if let Some(e) = secret_errors(&submission.secret) {
+        Some(e)
     } else if submission.version.is_empty() || submission.version.len() > 50 {
         Some("Version must be 1-50 bytes.")
     } else if submission.description.len() > 500 {
@@ -208,29 +589,180 @@ fn errors(submission: &Submission) -> Option<&'static str> {
         Some("Icon URL must be 500 bytes or less.")
     } else if submission.binary.len() > 500 {
         Some("Binary URL must be 500 bytes or less.")
+    } else if let Some(e) = file_errors(submission) {
+        Some(e)
     } else if submission.name.contains(is_invalid) {
         Some("Name must match [a-zA-Z0-9_-.].")
-    } else if submission.owner.contains(is_invalid) {
-        Some("Owner must match [a-zA-Z0-9_-.].")
     } else if semver::Version::parse(&submission.version).is_err() {
         Some("Version must comply with https://semver.org.")
     } else if !submission.homepage.is_empty()
         && url::Url::parse(&submission.homepage)
-            .and_then(|o| Ok(o.scheme() != "https"))
+            .map(|o| o.scheme() != "https")
             .unwrap_or(true)
     {
         Some("Homepage must be a URL using the HTTPS scheme.")
     } else if url::Url::parse(&submission.icon)
-        .and_then(|o| Ok(o.scheme() != "https"))
+        .map(|o| o.scheme() != "https")
         .unwrap_or(true)
     {
         Some("Icon must be a URL using the HTTPS scheme.")
+    } else if let Some(e) = crate::tags::errors(&submission.tags) {
+        Some(e)
+    } else if !is_requirement(&submission.game_versions) {
+        Some("Game versions must be a semver requirement of 100 bytes or less.")
+    } else if submission.game_versions.is_some()
+        && game_range_of(&submission.game_versions).is_none()
+    {
+        Some("Game versions must allow at least one version.")
+    } else if !is_requirement(&submission.loader_version) {
+        Some("Loader version must be a semver requirement of 100 bytes or less.")
     } else {
         None
     }
 }
 
-fn timestamp() -> i64 {
+fn file_errors(submission: &Submission) -> Option<&'static str> {
+    if submission.files.len() > 20 {
+        return Some("A release can have at most 20 files.");
+    }
+
+    let mut paths = BTreeSet::new();
+    for file in &submission.files {
+        let path = file_path(file);
+        if file.name.is_empty() || file.name.len() > 100 || file.name.contains(['/', '\\']) {
+            return Some("Each file name must be 1-100 bytes with no slashes.");
+        } else if file.url.len() > 500 {
+            return Some("Each file URL must be 500 bytes or less.");
+        } else if path.len() > 200 || !is_relative_path(&path) {
+            return Some("Each file path must be a relative path of 200 bytes or less.");
+        } else if !paths.insert(path.to_lowercase()) {
+            return Some("Each file must have a different path.");
+        }
+    }
+
+    if !submission.files.iter().any(|f| f.url == submission.binary) {
+        return Some("The binary must be one of the files.");
+    }
+    None
+}
+
+// Relative, forward-slashed, and unable to climb out of the game's folder
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains(['\\', ':'])
+        && path
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+fn file_role(file: &SubmittedFile) -> FileRole {
+    file.role.unwrap_or_else(|| FileRole::guess(&file.name))
+}
+
+fn file_path(file: &SubmittedFile) -> String {
+    match &file.path {
+        Some(path) => path.clone(),
+        None => file_role(file).path(&file.name),
+    }
+}
+
+// The last part of a URL's path if it's a file name. Otherwise, like for Google Drive links that
+// end in `uc` or `view`, the binary is assumed to be a DLL named after its mod so that different
+// mods don't install to the same path.
+fn default_name(url: &str, mod_name: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            u.path_segments()?
+                .rev()
+                .find(|s| !s.is_empty())
+                .filter(|s| s.contains('.'))
+                .map(str::to_owned)
+        })
+        .unwrap_or_else(|| format!("{mod_name}.dll"))
+}
+
+fn is_requirement(req: &Option<String>) -> bool {
+    match req {
+        Some(r) => r.len() <= 100 && semver::VersionReq::parse(r).is_ok(),
+        None => true,
+    }
+}
+
+fn game_range_of(req: &Option<String>) -> Option<VersionRange> {
+    let req = semver::VersionReq::parse(req.as_ref()?).ok()?;
+    crate::compat::range(&req)
+}
+
+/// The game versions a release supports, if it says.
+pub(crate) fn game_range(info: &ModInfo) -> Option<VersionRange> {
+    game_range_of(&info.game_versions)
+}
+
+pub(crate) fn owner_errors(owner: &str) -> Option<&'static str> {
+    if owner.is_empty() || owner.len() > 39 {
+        Some("Owner must be 1-39 bytes.")
+    } else if owner.contains(is_invalid) {
+        Some("Owner must match [a-zA-Z0-9_-.].")
+    } else {
+        None
+    }
+}
+
+pub(crate) fn secret_errors(secret: &str) -> Option<&'static str> {
+    if secret.is_empty() || secret.len() > 500 {
+        Some("Secret must be 1-500 bytes.")
+    } else {
+        None
+    }
+}
+
+async fn dependency_errors(
+    id: &str,
+    dependencies: &BTreeMap<String, String>,
+    mods: &Mods,
+) -> Option<&'static str> {
+    if dependencies.len() > 50 {
+        return Some("A mod can have at most 50 dependencies.");
+    }
+
+    for (dep, req) in dependencies {
+        if dep == id {
+            return Some("A mod can't depend on itself.");
+        } else if dep.len() > 79 || dep.split('/').count() != 2 {
+            return Some("Each dependency must be in the form `owner/name`.");
+        } else if req.len() > 50 || semver::VersionReq::parse(req).is_err() {
+            return Some("Each dependency version must be a semver requirement.");
+        }
+    }
+
+    let ids: Vec<String> = dependencies.keys().cloned().collect();
+    let existing = match mods.get_many(&ids).await {
+        Ok(v) => v,
+        Err(_) => return Some("Failed to look up dependencies."),
+    };
+
+    for (dep, req) in dependencies {
+        let req = semver::VersionReq::parse(req).expect("requirement was validated");
+        let mut matching = existing
+            .iter()
+            .filter(|e| &e.id == dep && e.deleted.is_none());
+        let satisfied = matching.any(|e| {
+            e.history()
+                .iter()
+                .filter_map(|r| semver::Version::parse(&r.info.version).ok())
+                .any(|v| req.matches(&v))
+        });
+
+        if !satisfied {
+            return Some("Each dependency must be an existing mod with a matching version.");
+        }
+    }
+
+    None
+}
+
+pub(crate) fn timestamp() -> i64 {
     use std::time::SystemTime;
 
     SystemTime::now()
@@ -239,7 +771,7 @@ fn timestamp() -> i64 {
         .as_secs() as i64
 }
 
-fn n_gram(s: &str, skip_n: usize) -> String {
+pub(crate) fn n_gram(s: &str, skip_n: usize) -> String {
     use unicode_segmentation::UnicodeSegmentation;
 
     let mut ret = String::with_capacity(s.len());
@@ -256,3 +788,31 @@ fn n_gram(s: &str, skip_n: usize) -> String {
     }
     ret
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_binaries_without_file_names_after_their_mod() {
+        assert_eq!(
+            default_name(
+                "https://github.com/a/b/releases/download/v1/CentiShields.dll",
+                "b"
+            ),
+            "CentiShields.dll"
+        );
+        assert_eq!(
+            default_name(
+                "https://drive.google.com/uc?export=download&id=1a2b3c",
+                "centipede-shields"
+            ),
+            "centipede-shields.dll"
+        );
+        assert_eq!(
+            default_name("https://drive.google.com/file/d/1a2b3c/view", "other-mod"),
+            "other-mod.dll"
+        );
+        assert_eq!(default_name("not a url", "b"), "b.dll");
+    }
+}
@@ -0,0 +1,331 @@
+use super::*;
+use crate::search;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+/// Keeps everything in memory. Nothing survives a restart.
+#[derive(Default)]
+pub(crate) struct MemoryStore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    mods: BTreeMap<String, ModEntry>,
+    // Deduplication keys of counted downloads, with when they were counted
+    downloads: HashMap<String, i64>,
+    icons: BTreeMap<String, Vec<u8>>,
+    audit: Vec<AuditEvent>,
+}
+
+impl MemoryStore {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("memory store poisoned")
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut ModEntry)) -> StoreResult<bool> {
+        Ok(self.lock().mods.get_mut(id).map(f).is_some())
+    }
+}
+
+#[rocket::async_trait]
+impl ModStore for MemoryStore {
+    async fn get(&self, id: &str) -> StoreResult<Option<ModEntry>> {
+        Ok(self.lock().mods.get(id).cloned())
+    }
+
+    async fn get_many(&self, ids: &[String]) -> StoreResult<Vec<ModEntry>> {
+        let inner = self.lock();
+        Ok(ids
+            .iter()
+            .filter_map(|id| inner.mods.get(id).cloned())
+            .collect())
+    }
+
+    async fn all(&self) -> StoreResult<Vec<ModEntry>> {
+        Ok(self.lock().mods.values().cloned().collect())
+    }
+
+    async fn list(&self, query: &ListQuery) -> StoreResult<Vec<ModEntry>> {
+        let terms = query_terms(query);
+        let inner = self.lock();
+        let key = |e: &ModEntry| match query.sort {
+            Sort::Relevance => search::score(&e.terms, &terms) as i64,
+            sort => sort.key(e),
+        };
+
+        let mut entries: Vec<&ModEntry> = inner
+            .mods
+            .values()
+            .filter(|e| matches_query(e, query, &terms))
+            .filter(|e| match &query.after {
+                Some(c) => order(query.sort, (key(e), &e.id), (c.key, &c.id)).is_gt(),
+                None => true,
+            })
+            .collect();
+
+        entries.sort_by(|a, b| order(query.sort, (key(a), &a.id), (key(b), &b.id)));
+
+        Ok(entries
+            .into_iter()
+            .skip(query.skip as usize)
+            .take(query.limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn count(&self) -> StoreResult<u64> {
+        Ok(self.lock().mods.values().filter(|e| is_listed(e)).count() as u64)
+    }
+
+    async fn count_matching(&self, query: &ListQuery) -> StoreResult<u64> {
+        let terms = query_terms(query);
+        let inner = self.lock();
+        Ok(inner
+            .mods
+            .values()
+            .filter(|e| matches_query(e, query, &terms))
+            .count() as u64)
+    }
+
+    async fn suggest(&self, words: &[String], limit: u64) -> StoreResult<Vec<String>> {
+        let inner = self.lock();
+        let sort = Sort::MostDownloads;
+
+        let mut entries: Vec<&ModEntry> = inner
+            .mods
+            .values()
+            .filter(|e| is_listed(e))
+            .filter(|e| {
+                let grams: Vec<String> =
+                    e.search.split_whitespace().map(str::to_lowercase).collect();
+                words.iter().all(|w| grams.contains(w))
+            })
+            .collect();
+
+        entries.sort_by(|a, b| order(sort, (sort.key(a), &a.id), (sort.key(b), &b.id)));
+
+        Ok(entries
+            .into_iter()
+            .take(limit as usize)
+            .map(|e| e.id.clone())
+            .collect())
+    }
+
+    async fn tag_counts(&self) -> StoreResult<HashMap<String, u64>> {
+        let mut counts = HashMap::new();
+
+        for entry in self.lock().mods.values().filter(|e| is_listed(e)) {
+            for tag in &entry.info.tags {
+                *counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn upsert_if_newer(&self, entry: &ModEntry) -> StoreResult<Upsert> {
+        let mut inner = self.lock();
+
+        match inner.mods.get_mut(&entry.id) {
+            Some(old) if old.removed.is_some() => Ok(Upsert::Removed),
+            Some(old) if !entry.newer(old) => Ok(Upsert::Outdated),
+            Some(old) => {
+                let releases = new_releases(Some(old), entry);
+                old.releases.extend(releases);
+                old.updated = entry.updated;
+                old.info = entry.info.clone();
+                old.terms = entry.terms.clone();
+                old.game_range = entry.game_range;
+                old.deleted = None;
+                old.last_checked = None;
+                old.broken = false;
+                old.withdrawn = false;
+                Ok(Upsert::Updated)
+            }
+            None => {
+                let mut entry = entry.clone();
+                entry.downloads = Some(0);
+                entry.releases = new_releases(None, &entry);
+                inner.mods.insert(entry.id.clone(), entry);
+                Ok(Upsert::Inserted)
+            }
+        }
+    }
+
+    async fn import(&self, entry: &ModEntry) -> StoreResult<()> {
+        self.lock().mods.insert(entry.id.clone(), entry.clone());
+        Ok(())
+    }
+
+    async fn set_secret(
+        &self,
+        id: &str,
+        old: Option<&str>,
+        secret: &str,
+        hook_key: &str,
+    ) -> StoreResult<bool> {
+        let mut inner = self.lock();
+
+        match inner.mods.get_mut(id) {
+            Some(e) if old.is_none() || old == Some(e.secret.as_str()) => {
+                e.secret = secret.to_owned();
+                e.hook_key = Some(hook_key.to_owned());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_unlisted(&self, id: &str, unlisted: bool) -> StoreResult<bool> {
+        self.update(id, |e| e.unlisted = unlisted)
+    }
+
+    async fn set_deleted(&self, id: &str, deleted: Option<i64>) -> StoreResult<bool> {
+        self.update(id, |e| e.deleted = deleted)
+    }
+
+    async fn set_removed(&self, id: &str, removed: Option<i64>) -> StoreResult<bool> {
+        self.update(id, |e| {
+            e.removed = removed;
+            e.deleted = removed;
+        })
+    }
+
+    async fn set_link_status(&self, id: &str, checked: i64, broken: bool) -> StoreResult<bool> {
+        self.update(id, |e| {
+            e.last_checked = Some(checked);
+            e.broken = broken;
+        })
+    }
+
+    async fn withdraw(&self, id: &str, version: &str) -> StoreResult<bool> {
+        match self.lock().mods.get_mut(id) {
+            Some(e) if e.info.version == version => {
+                e.withdrawn = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_prerelease(&self, id: &str, prerelease: Option<&Release>) -> StoreResult<bool> {
+        let prerelease = prerelease.cloned();
+        self.update(id, |e| e.prerelease = prerelease)
+    }
+
+    async fn icon(&self, id: &str) -> StoreResult<Option<Vec<u8>>> {
+        Ok(self.lock().icons.get(id).cloned())
+    }
+
+    async fn set_icon(&self, id: &str, png: &[u8]) -> StoreResult<()> {
+        self.lock().icons.insert(id.to_owned(), png.to_vec());
+        Ok(())
+    }
+
+    async fn rename(&self, id: &str, new_id: &str) -> StoreResult<bool> {
+        let mut inner = self.lock();
+
+        if inner.mods.contains_key(new_id) {
+            return Err(StoreError::Conflict);
+        }
+
+        match inner.mods.remove(id) {
+            Some(mut entry) => {
+                entry.id = new_id.to_owned();
+                entry.reindex();
+                inner.mods.insert(new_id.to_owned(), entry);
+                if let Some(icon) = inner.icons.remove(id) {
+                    inner.icons.insert(new_id.to_owned(), icon);
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn count_download(&self, id: &str, dedup: Option<String>) -> StoreResult<()> {
+        let mut inner = self.lock();
+
+        if let Some(key) = dedup {
+            let now = crate::serde::timestamp();
+            inner
+                .downloads
+                .retain(|_, &mut t| now - t < TRENDING_WINDOW);
+
+            match inner.downloads.entry(format!("{id} {key}")) {
+                Entry::Occupied(_) => return Ok(()),
+                Entry::Vacant(v) => v.insert(now),
+            };
+        }
+
+        if let Some(e) = inner.mods.get_mut(id) {
+            e.downloads = Some(e.downloads.unwrap_or(0) + 1);
+        }
+        Ok(())
+    }
+
+    async fn recent_downloads(&self, since: i64) -> StoreResult<HashMap<String, u32>> {
+        let mut counts = HashMap::new();
+
+        for (key, &time) in &self.lock().downloads {
+            // Keys are the mod's ID and the deduplication key, separated by a space
+            match key.split_once(' ') {
+                Some((id, _)) if time >= since => *counts.entry(id.to_owned()).or_insert(0) += 1,
+                _ => {}
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn set_trending(&self, id: &str, trending: u32) -> StoreResult<bool> {
+        self.update(id, |e| e.trending = trending)
+    }
+
+    async fn record_audit(&self, event: &AuditEvent) -> StoreResult<()> {
+        self.lock().audit.push(event.clone());
+        Ok(())
+    }
+
+    async fn audit_log(&self, id: &str, skip: u64, limit: u64) -> StoreResult<Vec<AuditEvent>> {
+        Ok(self
+            .lock()
+            .audit
+            .iter()
+            .rev()
+            .filter(|e| e.mod_id == id)
+            .skip(skip as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+fn query_terms(query: &ListQuery) -> Vec<String> {
+    query.search.map(search::query_terms).unwrap_or_default()
+}
+
+fn matches_query(entry: &ModEntry, query: &ListQuery, terms: &[String]) -> bool {
+    (query.hidden || is_listed(entry))
+        && (query.broken || !entry.broken)
+        && query.tags.iter().all(|t| entry.info.tags.contains(t))
+        && query
+            .game
+            .is_none_or(|v| entry.game_range.is_some_and(|r| r.contains(v)))
+        && (query.search.is_none() || search::score(&entry.terms, terms) >= search::MIN_SCORE)
+}
+
+// Where two entries' sort keys and IDs place them relative to each other
+fn order(sort: Sort, a: (i64, &String), b: (i64, &String)) -> Ordering {
+    let by_key = match sort.descending() {
+        true => b.0.cmp(&a.0),
+        false => a.0.cmp(&b.0),
+    };
+    by_key.then_with(|| a.1.cmp(b.1))
+}
+
+fn is_listed(entry: &ModEntry) -> bool {
+    !entry.unlisted && entry.deleted.is_none()
+}
@@ -0,0 +1,255 @@
+use crate::config::Config;
+use crate::serde::{AuditEvent, ModEntry, Release};
+use std::{collections::HashMap, fmt};
+
+pub(crate) mod memory;
+pub(crate) mod mongo;
+pub(crate) mod sqlite;
+#[cfg(test)]
+mod tests;
+
+pub(crate) type StoreResult<T> = Result<T, StoreError>;
+
+/// How far back downloads count towards trending. Download records are kept this long.
+pub(crate) const TRENDING_WINDOW: i64 = 7 * 24 * 60 * 60;
+
+/// Persistent storage for mod entries and everything recorded about them.
+#[rocket::async_trait]
+pub(crate) trait ModStore: Send + Sync {
+    /// Gets an entry by its `owner/name` ID, including deleted entries.
+    async fn get(&self, id: &str) -> StoreResult<Option<ModEntry>>;
+
+    /// Gets every entry whose ID is in `ids`, including deleted entries.
+    async fn get_many(&self, ids: &[String]) -> StoreResult<Vec<ModEntry>>;
+
+    /// Gets every entry, including deleted entries, in no particular order.
+    async fn all(&self) -> StoreResult<Vec<ModEntry>>;
+
+    /// Gets a page of entries matching the query.
+    async fn list(&self, query: &ListQuery) -> StoreResult<Vec<ModEntry>>;
+
+    /// Counts the entries that show up in listings.
+    async fn count(&self) -> StoreResult<u64>;
+
+    /// Counts every entry matching the query, ignoring where its page starts and ends.
+    async fn count_matching(&self, query: &ListQuery) -> StoreResult<u64>;
+
+    /// The IDs of listed entries whose `search` field has every word, most downloaded first.
+    async fn suggest(&self, words: &[String], limit: u64) -> StoreResult<Vec<String>>;
+
+    /// How many listed entries have each tag. Tags no entry has are left out.
+    async fn tag_counts(&self) -> StoreResult<HashMap<String, u64>>;
+
+    /// Inserts the entry, or updates the existing entry if the entry's release is newer and it
+    /// wasn't taken down. Updates keep the existing secret, downloads and publish date, append the
+    /// release to the entry's history, and forget the status of the old release's links.
+    async fn upsert_if_newer(&self, entry: &ModEntry) -> StoreResult<Upsert>;
+
+    /// Writes an entry exactly as given, replacing any entry with the same ID.
+    /// Only meant for copying entries between stores.
+    async fn import(&self, entry: &ModEntry) -> StoreResult<()>;
+
+    /// Replaces an entry's secret. If `old` is given, the stored secret must match it.
+    /// Returns whether the secret was replaced.
+    async fn set_secret(
+        &self,
+        id: &str,
+        old: Option<&str>,
+        secret: &str,
+        hook_key: &str,
+    ) -> StoreResult<bool>;
+
+    /// Returns whether the entry exists.
+    async fn set_unlisted(&self, id: &str, unlisted: bool) -> StoreResult<bool>;
+
+    /// Soft-deletes an entry at the given time, or restores it if `None`.
+    /// Returns whether the entry exists.
+    async fn set_deleted(&self, id: &str, deleted: Option<i64>) -> StoreResult<bool>;
+
+    /// Takes an entry down at the given time, or lifts the takedown if `None`. Also deletes or
+    /// restores it. New releases never lift a takedown. Returns whether the entry exists.
+    async fn set_removed(&self, id: &str, removed: Option<i64>) -> StoreResult<bool>;
+
+    /// Records the outcome of checking an entry's links.
+    /// Returns whether the entry exists.
+    async fn set_link_status(&self, id: &str, checked: i64, broken: bool) -> StoreResult<bool>;
+
+    /// Marks an entry's current release as withdrawn, if its version is still `version`.
+    /// Returns whether it was marked.
+    async fn withdraw(&self, id: &str, version: &str) -> StoreResult<bool>;
+
+    /// Replaces an entry's prerelease, or removes it if `None`.
+    /// Returns whether the entry exists.
+    async fn set_prerelease(&self, id: &str, prerelease: Option<&Release>) -> StoreResult<bool>;
+
+    /// Gets the mirrored copy of an entry's icon.
+    async fn icon(&self, id: &str) -> StoreResult<Option<Vec<u8>>>;
+
+    /// Replaces the mirrored copy of an entry's icon.
+    async fn set_icon(&self, id: &str, png: &[u8]) -> StoreResult<()>;
+
+    /// Moves an entry to a new ID, along with its icon. Fails with [`StoreError::Conflict`] if the ID is taken.
+    /// Returns whether the entry exists.
+    async fn rename(&self, id: &str, new_id: &str) -> StoreResult<bool>;
+
+    /// Increments an entry's downloads, unless a download with the same `dedup` key was
+    /// already counted for it.
+    async fn count_download(&self, id: &str, dedup: Option<String>) -> StoreResult<()>;
+
+    /// Counts each entry's deduplicated downloads since the given time.
+    async fn recent_downloads(&self, since: i64) -> StoreResult<HashMap<String, u32>>;
+
+    /// Returns whether the entry exists.
+    async fn set_trending(&self, id: &str, trending: u32) -> StoreResult<bool>;
+
+    async fn record_audit(&self, event: &AuditEvent) -> StoreResult<()>;
+
+    /// Gets an entry's audit events, newest first.
+    async fn audit_log(&self, id: &str, skip: u64, limit: u64) -> StoreResult<Vec<AuditEvent>>;
+}
+
+pub(crate) enum Upsert {
+    Inserted,
+    Updated,
+    Outdated,
+    Removed,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Sort {
+    New,
+    Old,
+    MostDownloads,
+    LeastDownloads,
+    // How well an entry matches the search, best first. Needs a search.
+    Relevance,
+    // By ID, alphabetically
+    Name,
+    PublishedNew,
+    PublishedOld,
+    // Most downloads within the trending window first
+    Trending,
+}
+
+impl Sort {
+    /// The value entries are ordered by. Ties are broken by ID, in ascending order.
+    /// Relevance depends on the search, so it has no key of its own.
+    pub fn key(self, entry: &ModEntry) -> i64 {
+        match self {
+            Sort::New | Sort::Old => entry.updated,
+            Sort::MostDownloads | Sort::LeastDownloads => entry.downloads.unwrap_or(0) as i64,
+            Sort::PublishedNew | Sort::PublishedOld => entry.published,
+            Sort::Trending => entry.trending as i64,
+            Sort::Relevance | Sort::Name => 0,
+        }
+    }
+
+    pub fn descending(self) -> bool {
+        matches!(
+            self,
+            Sort::New | Sort::MostDownloads | Sort::Relevance | Sort::PublishedNew | Sort::Trending
+        )
+    }
+
+    /// Whether pages can start after a [`Cursor`]. Otherwise, they have to skip entries.
+    pub fn keyset(self) -> bool {
+        self != Sort::Relevance
+    }
+}
+
+/// The last entry of a page. The next page starts right after it in the sort order.
+pub(crate) struct Cursor {
+    pub key: i64,
+    pub id: String,
+}
+
+pub(crate) struct ListQuery<'a> {
+    pub sort: Sort,
+    // Matches entries that score at least `search::MIN_SCORE` against the search
+    pub search: Option<&'a str>,
+    // Matches entries with every one of these tags
+    pub tags: &'a [String],
+    // Matches entries that support this packed game version
+    pub game: Option<i64>,
+    pub skip: u64,
+    pub limit: u64,
+    // Starts the page after this entry, rather than after skipping entries
+    pub after: Option<Cursor>,
+    // Whether to include unlisted and deleted entries
+    pub hidden: bool,
+    // Whether to include entries with dead links
+    pub broken: bool,
+}
+
+#[derive(Debug)]
+pub(crate) enum StoreError {
+    Mongo(mongodb::error::Error),
+    Sqlite(rusqlite::Error),
+    // A write collided with an existing entry
+    Conflict,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Mongo(e) => e.fmt(f),
+            StoreError::Sqlite(e) => e.fmt(f),
+            StoreError::Conflict => f.write_str("the entry already exists"),
+        }
+    }
+}
+
+impl From<mongodb::error::Error> for StoreError {
+    fn from(e: mongodb::error::Error) -> Self {
+        StoreError::Mongo(e)
+    }
+}
+
+/// Opens the store a `DB_URL` points to: `memory://`, `sqlite://<path>`, or a MongoDB URL.
+pub(crate) async fn open(url: &str, config: &Config) -> Box<dyn ModStore> {
+    if url == "memory://" {
+        println!("Using in-memory store");
+        Box::new(memory::MemoryStore::default())
+    } else if let Some(path) = url.strip_prefix("sqlite://") {
+        println!("Opening SQLite database at {path}");
+        Box::new(sqlite::SqliteStore::open(path))
+    } else {
+        println!("Connecting MongoDB client");
+        Box::new(mongo::MongoStore::connect(url, &config.database, &config.collection).await)
+    }
+}
+
+/// Copies every entry, its icon, and its audit log from one store to another.
+pub(crate) async fn migrate(from: &dyn ModStore, to: &dyn ModStore) -> StoreResult<usize> {
+    let entries = from.all().await?;
+
+    for entry in &entries {
+        to.import(entry).await?;
+
+        if let Some(icon) = from.icon(&entry.id).await? {
+            to.set_icon(&entry.id, &icon).await?;
+        }
+
+        // Replayed oldest first so the copies keep their order
+        let events = from.audit_log(&entry.id, 0, i64::MAX as u64).await?;
+        for event in events.iter().rev() {
+            to.record_audit(event).await?;
+        }
+    }
+
+    Ok(entries.len())
+}
+
+/// The releases to append to `old`'s history when `entry` replaces it.
+/// Entries from before release history was kept need their current release preserved too.
+pub(crate) fn new_releases(old: Option<&ModEntry>, entry: &ModEntry) -> Vec<Release> {
+    let mut releases = match old {
+        Some(old) if old.releases.is_empty() => old.history(),
+        _ => Vec::new(),
+    };
+    releases.push(Release {
+        published: entry.updated,
+        info: entry.info.clone(),
+    });
+    releases
+}
@@ -0,0 +1,487 @@
+use super::*;
+use crate::search;
+use crate::serde::{DownloadRecord, Release};
+use mongodb::{
+    bson::{doc, from_document, spec::BinarySubtype, to_bson, Binary, Bson, DateTime, Document},
+    error::{ErrorKind, WriteFailure},
+    options::{ClientOptions, FindOptions, ReplaceOptions},
+    Client, Collection,
+};
+use rocket::futures::TryStreamExt;
+
+const DUPLICATE_KEY: i32 = 11000;
+
+pub(crate) struct MongoStore {
+    mods: Collection<ModEntry>,
+    downloads: Collection<DownloadRecord>,
+    audit: Collection<AuditEvent>,
+    icons: Collection<Document>,
+}
+
+impl MongoStore {
+    pub async fn connect(conn: &str, database: &str, collection: &str) -> Self {
+        let options = ClientOptions::parse(conn)
+            .await
+            .expect("invalid connection url");
+
+        let client = Client::with_options(options).expect("failed to connect MongoDB client");
+        let db = client.database(database);
+        let store = Self {
+            mods: db.collection(collection),
+            downloads: db.collection("downloads"),
+            audit: db.collection("audit"),
+            icons: db.collection("icons"),
+        };
+
+        crate::indexes::add_indexes(&store.mods).await;
+        crate::indexes::add_download_indexes(&store.downloads).await;
+        crate::indexes::add_audit_indexes(&store.audit).await;
+
+        store
+    }
+
+    async fn update(&self, id: &str, update: Document) -> StoreResult<bool> {
+        let r = self
+            .mods
+            .update_one(doc! { "_id": id }, update, None)
+            .await?;
+        Ok(r.matched_count > 0)
+    }
+}
+
+#[rocket::async_trait]
+impl ModStore for MongoStore {
+    async fn get(&self, id: &str) -> StoreResult<Option<ModEntry>> {
+        Ok(self.mods.find_one(doc! { "_id": id }, None).await?)
+    }
+
+    async fn get_many(&self, ids: &[String]) -> StoreResult<Vec<ModEntry>> {
+        let cursor = self.mods.find(doc! { "_id": { "$in": ids } }, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn all(&self) -> StoreResult<Vec<ModEntry>> {
+        let cursor = self.mods.find(doc! {}, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn list(&self, query: &ListQuery) -> StoreResult<Vec<ModEntry>> {
+        let mut pipeline = pipeline(query);
+        let mut filter = doc! {};
+
+        if let (Sort::Name, Some(cursor)) = (query.sort, &query.after) {
+            filter.insert("_id", doc! { "$gt": &cursor.id });
+        } else if let Some(cursor) = &query.after {
+            let field = sort_field(query.sort);
+            let op = if query.sort.descending() {
+                "$lt"
+            } else {
+                "$gt"
+            };
+            filter.insert(
+                "$or",
+                vec![
+                    doc! { field: { op: cursor.key } },
+                    doc! { field: cursor.key, "_id": { "$gt": &cursor.id } },
+                ],
+            );
+        }
+
+        pipeline.extend([
+            doc! { "$match": filter },
+            doc! { "$sort": sort(query.sort) },
+            doc! { "$skip": query.skip.min(i64::MAX as u64) as i64 },
+            doc! { "$limit": query.limit as i64 },
+        ]);
+
+        let docs: Vec<Document> = self
+            .mods
+            .aggregate(pipeline, None)
+            .await?
+            .try_collect()
+            .await?;
+        docs.into_iter()
+            .map(|d| from_document(d).map_err(|e| StoreError::Mongo(e.into())))
+            .collect()
+    }
+
+    async fn count(&self) -> StoreResult<u64> {
+        Ok(self.mods.count_documents(listed(), None).await?)
+    }
+
+    async fn count_matching(&self, query: &ListQuery) -> StoreResult<u64> {
+        let mut pipeline = pipeline(query);
+        pipeline.push(doc! { "$count": "count" });
+
+        let mut cursor = self.mods.aggregate(pipeline, None).await?;
+        let count = match cursor.try_next().await? {
+            Some(doc) => doc.get_i32("count").unwrap_or(0) as u64,
+            None => 0,
+        };
+        Ok(count)
+    }
+
+    async fn suggest(&self, words: &[String], limit: u64) -> StoreResult<Vec<String>> {
+        // Quoted words are all required, where bare words would match any of them
+        let search: Vec<String> = words.iter().map(|w| format!("\"{w}\"")).collect();
+        let mut filter = listed();
+        filter.insert("$text", doc! { "$search": search.join(" ") });
+
+        // Only IDs are needed, which keeps this cheap enough to call on every keystroke
+        let options = FindOptions::builder()
+            .projection(doc! { "_id": 1 })
+            .sort(doc! { "downloads": -1, "_id": 1 })
+            .limit(limit as i64)
+            .build();
+
+        let docs: Vec<Document> = self
+            .mods
+            .clone_with_type::<Document>()
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(docs
+            .into_iter()
+            .filter_map(|d| d.get_str("_id").ok().map(str::to_owned))
+            .collect())
+    }
+
+    async fn tag_counts(&self) -> StoreResult<HashMap<String, u64>> {
+        let pipeline = [
+            doc! { "$match": listed() },
+            doc! { "$unwind": "$info.tags" },
+            doc! { "$group": { "_id": "$info.tags", "count": { "$sum": 1 } } },
+        ];
+
+        let mut cursor = self.mods.aggregate(pipeline, None).await?;
+        let mut counts = HashMap::new();
+
+        while let Some(group) = cursor.try_next().await? {
+            if let (Ok(tag), Ok(count)) = (group.get_str("_id"), group.get_i32("count")) {
+                counts.insert(tag.to_owned(), count as u64);
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn upsert_if_newer(&self, entry: &ModEntry) -> StoreResult<Upsert> {
+        // Writes are conditional on what was read, so a concurrent write means trying again
+        loop {
+            let old = match self.get(&entry.id).await? {
+                Some(old) => old,
+                None => {
+                    let mut entry = entry.clone();
+                    entry.downloads = Some(0);
+                    entry.releases = new_releases(None, &entry);
+
+                    match self.mods.insert_one(&entry, None).await {
+                        Ok(_) => return Ok(Upsert::Inserted),
+                        Err(e) if is_duplicate(&e) => continue,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            };
+
+            if old.removed.is_some() {
+                return Ok(Upsert::Removed);
+            }
+            if !entry.newer(&old) {
+                return Ok(Upsert::Outdated);
+            }
+
+            let releases: Vec<Release> = new_releases(Some(&old), entry);
+            let query = doc! {
+                "_id": &entry.id,
+                "info.version": &old.info.version,
+                "removed": { "$exists": false }
+            };
+            let update = doc! {
+                "$set": {
+                    "updated": entry.updated,
+                    "info": to_bson(&entry.info).expect("Failed to deser ModInfo"),
+                    "terms": to_bson(&entry.terms).expect("Failed to deser Term"),
+                    "game_range": to_bson(&entry.game_range).expect("Failed to deser VersionRange"),
+                    "broken": false,
+                    "withdrawn": false
+                },
+                "$unset": {
+                    "deleted": "",
+                    "last_checked": ""
+                },
+                "$push": {
+                    "releases": { "$each": to_bson(&releases).expect("Failed to deser Release") }
+                }
+            };
+
+            if self
+                .mods
+                .update_one(query, update, None)
+                .await?
+                .matched_count
+                > 0
+            {
+                return Ok(Upsert::Updated);
+            }
+        }
+    }
+
+    async fn import(&self, entry: &ModEntry) -> StoreResult<()> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.mods
+            .replace_one(doc! { "_id": &entry.id }, entry, options)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_secret(
+        &self,
+        id: &str,
+        old: Option<&str>,
+        secret: &str,
+        hook_key: &str,
+    ) -> StoreResult<bool> {
+        let mut query = doc! { "_id": id };
+        if let Some(old) = old {
+            query.insert("secret", old);
+        }
+        let update = doc! { "$set": { "secret": secret, "hook_key": hook_key } };
+
+        Ok(self
+            .mods
+            .update_one(query, update, None)
+            .await?
+            .matched_count
+            > 0)
+    }
+
+    async fn set_unlisted(&self, id: &str, unlisted: bool) -> StoreResult<bool> {
+        self.update(id, doc! { "$set": { "unlisted": unlisted } })
+            .await
+    }
+
+    async fn set_deleted(&self, id: &str, deleted: Option<i64>) -> StoreResult<bool> {
+        match deleted {
+            Some(time) => self.update(id, doc! { "$set": { "deleted": time } }).await,
+            None => self.update(id, doc! { "$unset": { "deleted": "" } }).await,
+        }
+    }
+
+    async fn set_removed(&self, id: &str, removed: Option<i64>) -> StoreResult<bool> {
+        match removed {
+            Some(time) => {
+                self.update(id, doc! { "$set": { "removed": time, "deleted": time } })
+                    .await
+            }
+            None => {
+                self.update(id, doc! { "$unset": { "removed": "", "deleted": "" } })
+                    .await
+            }
+        }
+    }
+
+    async fn set_link_status(&self, id: &str, checked: i64, broken: bool) -> StoreResult<bool> {
+        self.update(
+            id,
+            doc! { "$set": { "last_checked": checked, "broken": broken } },
+        )
+        .await
+    }
+
+    async fn withdraw(&self, id: &str, version: &str) -> StoreResult<bool> {
+        let r = self
+            .mods
+            .update_one(
+                doc! { "_id": id, "info.version": version },
+                doc! { "$set": { "withdrawn": true } },
+                None,
+            )
+            .await?;
+        Ok(r.matched_count > 0)
+    }
+
+    async fn set_prerelease(&self, id: &str, prerelease: Option<&Release>) -> StoreResult<bool> {
+        match prerelease {
+            Some(release) => {
+                let release = to_bson(release).expect("Failed to deser Release");
+                self.update(id, doc! { "$set": { "prerelease": release } })
+                    .await
+            }
+            None => {
+                self.update(id, doc! { "$unset": { "prerelease": "" } })
+                    .await
+            }
+        }
+    }
+
+    async fn icon(&self, id: &str) -> StoreResult<Option<Vec<u8>>> {
+        let icon = self.icons.find_one(doc! { "_id": id }, None).await?;
+        Ok(icon.and_then(|mut d| match d.remove("png") {
+            Some(Bson::Binary(b)) => Some(b.bytes),
+            _ => None,
+        }))
+    }
+
+    async fn set_icon(&self, id: &str, png: &[u8]) -> StoreResult<()> {
+        let png = Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: png.to_vec(),
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+
+        self.icons
+            .replace_one(doc! { "_id": id }, doc! { "_id": id, "png": png }, options)
+            .await?;
+        Ok(())
+    }
+
+    async fn rename(&self, id: &str, new_id: &str) -> StoreResult<bool> {
+        let mut entry = match self.get(id).await? {
+            Some(e) => e,
+            None => return Ok(false),
+        };
+
+        // An `_id` can't change, so the entry is copied to its new ID before removing the old one
+        entry.id = new_id.to_owned();
+        entry.reindex();
+
+        match self.mods.insert_one(&entry, None).await {
+            Ok(_) => {}
+            Err(e) if is_duplicate(&e) => return Err(StoreError::Conflict),
+            Err(e) => return Err(e.into()),
+        }
+
+        self.mods.delete_one(doc! { "_id": id }, None).await?;
+
+        if let Some(icon) = self.icon(id).await? {
+            self.set_icon(new_id, &icon).await?;
+            self.icons.delete_one(doc! { "_id": id }, None).await?;
+        }
+        Ok(true)
+    }
+
+    async fn count_download(&self, id: &str, dedup: Option<String>) -> StoreResult<()> {
+        if let Some(key) = dedup {
+            let record = DownloadRecord {
+                id: format!("{id} {key}"),
+                created: DateTime::now(),
+                mod_id: id.to_owned(),
+            };
+
+            match self.downloads.insert_one(record, None).await {
+                Ok(_) => {}
+                Err(e) if is_duplicate(&e) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.update(id, doc! { "$inc": { "downloads": 1 } })
+            .await
+            .map(|_| ())
+    }
+
+    async fn recent_downloads(&self, since: i64) -> StoreResult<HashMap<String, u32>> {
+        let pipeline = [
+            doc! { "$match": {
+                "created": { "$gte": DateTime::from_millis(since * 1000) }
+            } },
+            doc! { "$group": { "_id": "$mod_id", "count": { "$sum": 1 } } },
+        ];
+
+        let mut cursor = self.downloads.aggregate(pipeline, None).await?;
+        let mut counts = HashMap::new();
+
+        while let Some(group) = cursor.try_next().await? {
+            if let (Ok(id), Ok(count)) = (group.get_str("_id"), group.get_i32("count")) {
+                counts.insert(id.to_owned(), count as u32);
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn set_trending(&self, id: &str, trending: u32) -> StoreResult<bool> {
+        self.update(id, doc! { "$set": { "trending": trending } })
+            .await
+    }
+
+    async fn record_audit(&self, event: &AuditEvent) -> StoreResult<()> {
+        self.audit.insert_one(event, None).await?;
+        Ok(())
+    }
+
+    async fn audit_log(&self, id: &str, skip: u64, limit: u64) -> StoreResult<Vec<AuditEvent>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "time": -1 })
+            .skip(skip)
+            .limit(limit as i64)
+            .build();
+
+        let cursor = self.audit.find(doc! { "mod_id": id }, options).await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
+
+// Matches every mod that should show up in listings
+fn listed() -> Document {
+    doc! { "unlisted": { "$ne": true }, "deleted": { "$exists": false } }
+}
+
+// Matches every mod the query could list, on any page, and scores them against the search
+fn pipeline(query: &ListQuery) -> Vec<Document> {
+    let mut filter = match query.hidden {
+        true => doc! {},
+        false => listed(),
+    };
+    if !query.broken {
+        filter.insert("broken", doc! { "$ne": true });
+    }
+    if let Some(version) = query.game {
+        filter.insert("game_range.min", doc! { "$lte": version });
+        filter.insert("game_range.max", doc! { "$gt": version });
+    }
+    if !query.tags.is_empty() {
+        filter.insert("info.tags", doc! { "$all": query.tags });
+    }
+
+    let search = match query.search {
+        Some(s) => search::query_terms(s),
+        None => return vec![doc! { "$match": filter }],
+    };
+
+    // Narrowed down with the index on `terms.term` before anything is scored
+    filter.insert("terms.term", doc! { "$in": &search });
+    let matched = doc! {
+        "$filter": { "input": "$terms", "cond": { "$in": ["$$this.term", &search] } }
+    };
+
+    vec![
+        doc! { "$match": filter },
+        doc! { "$addFields": { "score": { "$sum": { "$map": { "input": matched, "in": "$$this.weight" } } } } },
+        doc! { "$match": { "score": { "$gte": search::MIN_SCORE } } },
+    ]
+}
+
+fn sort_field(sort: Sort) -> &'static str {
+    match sort {
+        Sort::New | Sort::Old => "updated",
+        Sort::MostDownloads | Sort::LeastDownloads => "downloads",
+        Sort::PublishedNew | Sort::PublishedOld => "published",
+        Sort::Trending => "trending",
+        // Every ID is unique, so its tie-breaker is all there is
+        Sort::Name => "_id",
+        Sort::Relevance => "score",
+    }
+}
+
+fn sort(sort: Sort) -> Document {
+    let order = if sort.descending() { -1 } else { 1 };
+    match sort {
+        Sort::Relevance => doc! { "score": -1, "_id": 1 },
+        Sort::Name => doc! { "_id": 1 },
+        _ => doc! { sort_field(sort): order, "_id": 1 },
+    }
+}
+
+fn is_duplicate(e: &mongodb::error::Error) -> bool {
+    matches!(*e.kind, ErrorKind::Write(WriteFailure::WriteError(ref w)) if w.code == DUPLICATE_KEY)
+}
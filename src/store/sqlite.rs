@@ -0,0 +1,634 @@
+use super::*;
+use crate::search;
+use rocket::serde::json::serde_json;
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension};
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS mods (
+    id TEXT PRIMARY KEY,
+    updated INTEGER NOT NULL,
+    downloads INTEGER NOT NULL,
+    listed INTEGER NOT NULL,
+    entry TEXT NOT NULL,
+    published INTEGER NOT NULL,
+    trending INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS mods_updated ON mods (updated);
+CREATE INDEX IF NOT EXISTS mods_downloads ON mods (downloads);
+CREATE INDEX IF NOT EXISTS mods_published ON mods (published);
+CREATE INDEX IF NOT EXISTS mods_trending ON mods (trending);
+
+CREATE TABLE IF NOT EXISTS mod_words (
+    word TEXT NOT NULL,
+    id TEXT NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    weight INTEGER NOT NULL,
+    PRIMARY KEY (word, id)
+);
+CREATE INDEX IF NOT EXISTS mod_words_id ON mod_words (id);
+
+CREATE TABLE IF NOT EXISTS mod_grams (
+    gram TEXT NOT NULL,
+    id TEXT NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    PRIMARY KEY (gram, id)
+);
+CREATE INDEX IF NOT EXISTS mod_grams_id ON mod_grams (id);
+
+CREATE TABLE IF NOT EXISTS mod_tags (
+    tag TEXT NOT NULL,
+    id TEXT NOT NULL REFERENCES mods (id) ON DELETE CASCADE,
+    PRIMARY KEY (tag, id)
+);
+CREATE INDEX IF NOT EXISTS mod_tags_id ON mod_tags (id);
+
+CREATE TABLE IF NOT EXISTS icons (
+    id TEXT PRIMARY KEY,
+    png BLOB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS downloads (
+    key TEXT PRIMARY KEY,
+    created INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS audit (
+    mod_id TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    event TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS audit_mod_id ON audit (mod_id, time);
+";
+
+/// Keeps everything in a single SQLite database file. Entries are stored as JSON, next to the
+/// columns that listings filter and sort by.
+pub(crate) struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Self {
+        let conn = Connection::open(path).expect("failed to open SQLite database");
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")
+            .expect("failed to configure SQLite database");
+        conn.execute_batch(SCHEMA)
+            .expect("failed to create SQLite tables");
+
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    // SQLite calls block, so they run off the async executor
+    async fn with<T, F>(&self, f: F) -> StoreResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+
+        rocket::tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().expect("SQLite connection poisoned");
+            f(&mut conn)
+        })
+        .await
+        .expect("SQLite task panicked")
+        .map_err(StoreError::Sqlite)
+    }
+
+    // Only for changes that leave the searchable fields alone
+    async fn update(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut ModEntry) + Send + 'static,
+    ) -> StoreResult<bool> {
+        let id = id.to_owned();
+
+        self.with(move |conn| {
+            let tx = conn.transaction()?;
+            let found = match get(&tx, &id)? {
+                Some(mut entry) => {
+                    f(&mut entry);
+                    put_row(&tx, &entry)?;
+                    true
+                }
+                None => false,
+            };
+            tx.commit()?;
+            Ok(found)
+        })
+        .await
+    }
+}
+
+#[rocket::async_trait]
+impl ModStore for SqliteStore {
+    async fn get(&self, id: &str) -> StoreResult<Option<ModEntry>> {
+        let id = id.to_owned();
+        self.with(move |conn| get(conn, &id)).await
+    }
+
+    async fn get_many(&self, ids: &[String]) -> StoreResult<Vec<ModEntry>> {
+        let ids = ids.to_vec();
+
+        self.with(move |conn| {
+            let mut entries = Vec::new();
+            for id in &ids {
+                entries.extend(get(conn, id)?);
+            }
+            Ok(entries)
+        })
+        .await
+    }
+
+    async fn all(&self) -> StoreResult<Vec<ModEntry>> {
+        self.with(|conn| {
+            let mut stmt = conn.prepare("SELECT entry FROM mods")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.map(|json| json.and_then(|json| from_json(&json)))
+                .collect()
+        })
+        .await
+    }
+
+    async fn list(&self, query: &ListQuery) -> StoreResult<Vec<ModEntry>> {
+        let (mut sql, mut args) = filter("SELECT entry", query);
+        let column = sort_column(query.sort);
+        let order = if query.sort.descending() {
+            "DESC"
+        } else {
+            "ASC"
+        };
+
+        if let (Sort::Name, Some(cursor)) = (query.sort, &query.after) {
+            sql.push_str(" AND id > ?");
+            args.push(Value::Text(cursor.id.clone()));
+        } else if let Some(cursor) = &query.after {
+            let op = if query.sort.descending() { "<" } else { ">" };
+            sql.push_str(&format!(
+                " AND ({column} {op} ? OR ({column} = ? AND id > ?))"
+            ));
+            args.extend([
+                Value::Integer(cursor.key),
+                Value::Integer(cursor.key),
+                Value::Text(cursor.id.clone()),
+            ]);
+        }
+
+        if let (Sort::Relevance, Some(search)) = (query.sort, query.search) {
+            let (score, terms) = score(search);
+            sql.push_str(&format!(" ORDER BY ({score}) DESC,"));
+            args.extend(terms);
+        } else {
+            sql.push_str(&format!(" ORDER BY {column} {order},"));
+        }
+
+        sql.push_str(&format!(
+            " id ASC LIMIT {} OFFSET {}",
+            query.limit.min(i64::MAX as u64),
+            query.skip.min(i64::MAX as u64)
+        ));
+
+        self.with(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(params_from_iter(args), |row| row.get::<_, String>(0))?;
+            rows.map(|json| json.and_then(|json| from_json(&json)))
+                .collect()
+        })
+        .await
+    }
+
+    async fn count(&self) -> StoreResult<u64> {
+        self.with(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM mods WHERE listed = 1", [], |row| {
+                row.get::<_, i64>(0)
+            })
+        })
+        .await
+        .map(|n| n as u64)
+    }
+
+    async fn count_matching(&self, query: &ListQuery) -> StoreResult<u64> {
+        let (sql, args) = filter("SELECT COUNT(*)", query);
+
+        self.with(move |conn| {
+            conn.query_row(&sql, params_from_iter(args), |row| row.get::<_, i64>(0))
+        })
+        .await
+        .map(|n| n as u64)
+    }
+
+    async fn suggest(&self, words: &[String], limit: u64) -> StoreResult<Vec<String>> {
+        let mut sql = "SELECT id FROM mods WHERE listed = 1".to_owned();
+        for _ in words {
+            sql.push_str(" AND id IN (SELECT id FROM mod_grams WHERE gram = ?)");
+        }
+        sql.push_str(&format!(
+            " ORDER BY downloads DESC, id ASC LIMIT {}",
+            limit.min(i64::MAX as u64)
+        ));
+        let words = words.to_vec();
+
+        self.with(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(params_from_iter(words), |row| row.get(0))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    async fn tag_counts(&self) -> StoreResult<HashMap<String, u64>> {
+        self.with(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT tag, COUNT(*) FROM mod_tags JOIN mods USING (id) WHERE listed = 1 GROUP BY tag",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    async fn upsert_if_newer(&self, entry: &ModEntry) -> StoreResult<Upsert> {
+        let entry = entry.clone();
+
+        self.with(move |conn| {
+            let tx = conn.transaction()?;
+            let upsert = match get(&tx, &entry.id)? {
+                Some(old) if old.removed.is_some() => Upsert::Removed,
+                Some(old) if !entry.newer(&old) => Upsert::Outdated,
+                Some(mut old) => {
+                    let releases = new_releases(Some(&old), &entry);
+                    old.releases.extend(releases);
+                    old.updated = entry.updated;
+                    old.info = entry.info.clone();
+                    old.terms = entry.terms.clone();
+                    old.game_range = entry.game_range;
+                    old.deleted = None;
+                    old.last_checked = None;
+                    old.broken = false;
+                    old.withdrawn = false;
+                    put(&tx, &old)?;
+                    Upsert::Updated
+                }
+                None => {
+                    let mut entry = entry;
+                    entry.downloads = Some(0);
+                    entry.releases = new_releases(None, &entry);
+                    put(&tx, &entry)?;
+                    Upsert::Inserted
+                }
+            };
+            tx.commit()?;
+            Ok(upsert)
+        })
+        .await
+    }
+
+    async fn import(&self, entry: &ModEntry) -> StoreResult<()> {
+        let entry = entry.clone();
+        self.with(move |conn| put(conn, &entry)).await
+    }
+
+    async fn set_secret(
+        &self,
+        id: &str,
+        old: Option<&str>,
+        secret: &str,
+        hook_key: &str,
+    ) -> StoreResult<bool> {
+        let (id, old) = (id.to_owned(), old.map(str::to_owned));
+        let (secret, hook_key) = (secret.to_owned(), hook_key.to_owned());
+
+        self.with(move |conn| {
+            let tx = conn.transaction()?;
+            let replaced = match get(&tx, &id)? {
+                Some(mut e) if old.is_none() || old.as_ref() == Some(&e.secret) => {
+                    e.secret = secret;
+                    e.hook_key = Some(hook_key);
+                    put_row(&tx, &e)?;
+                    true
+                }
+                _ => false,
+            };
+            tx.commit()?;
+            Ok(replaced)
+        })
+        .await
+    }
+
+    async fn set_unlisted(&self, id: &str, unlisted: bool) -> StoreResult<bool> {
+        self.update(id, move |e| e.unlisted = unlisted).await
+    }
+
+    async fn set_deleted(&self, id: &str, deleted: Option<i64>) -> StoreResult<bool> {
+        self.update(id, move |e| e.deleted = deleted).await
+    }
+
+    async fn set_removed(&self, id: &str, removed: Option<i64>) -> StoreResult<bool> {
+        self.update(id, move |e| {
+            e.removed = removed;
+            e.deleted = removed;
+        })
+        .await
+    }
+
+    async fn set_link_status(&self, id: &str, checked: i64, broken: bool) -> StoreResult<bool> {
+        self.update(id, move |e| {
+            e.last_checked = Some(checked);
+            e.broken = broken;
+        })
+        .await
+    }
+
+    async fn withdraw(&self, id: &str, version: &str) -> StoreResult<bool> {
+        let (id, version) = (id.to_owned(), version.to_owned());
+
+        self.with(move |conn| {
+            let tx = conn.transaction()?;
+            let withdrawn = match get(&tx, &id)? {
+                Some(mut e) if e.info.version == version => {
+                    e.withdrawn = true;
+                    put_row(&tx, &e)?;
+                    true
+                }
+                _ => false,
+            };
+            tx.commit()?;
+            Ok(withdrawn)
+        })
+        .await
+    }
+
+    async fn set_prerelease(&self, id: &str, prerelease: Option<&Release>) -> StoreResult<bool> {
+        let prerelease = prerelease.cloned();
+        self.update(id, move |e| e.prerelease = prerelease).await
+    }
+
+    async fn icon(&self, id: &str) -> StoreResult<Option<Vec<u8>>> {
+        let id = id.to_owned();
+
+        self.with(move |conn| {
+            conn.query_row("SELECT png FROM icons WHERE id = ?", [id], |row| row.get(0))
+                .optional()
+        })
+        .await
+    }
+
+    async fn set_icon(&self, id: &str, png: &[u8]) -> StoreResult<()> {
+        let (id, png) = (id.to_owned(), png.to_vec());
+
+        self.with(move |conn| {
+            conn.execute(
+                "INSERT INTO icons (id, png) VALUES (?1, ?2) ON CONFLICT (id) DO UPDATE SET png = ?2",
+                params![id, png],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    async fn rename(&self, id: &str, new_id: &str) -> StoreResult<bool> {
+        let (id, new_id) = (id.to_owned(), new_id.to_owned());
+
+        let renamed = self
+            .with(move |conn| {
+                let tx = conn.transaction()?;
+                if get(&tx, &new_id)?.is_some() {
+                    return Ok(None);
+                }
+
+                let renamed = match get(&tx, &id)? {
+                    Some(mut entry) => {
+                        tx.execute("DELETE FROM mods WHERE id = ?", [&id])?;
+                        tx.execute("UPDATE icons SET id = ? WHERE id = ?", [&new_id, &id])?;
+                        entry.id = new_id.clone();
+                        entry.reindex();
+                        put(&tx, &entry)?;
+                        true
+                    }
+                    None => false,
+                };
+                tx.commit()?;
+                Ok(Some(renamed))
+            })
+            .await?;
+
+        renamed.ok_or(StoreError::Conflict)
+    }
+
+    async fn count_download(&self, id: &str, dedup: Option<String>) -> StoreResult<()> {
+        let id = id.to_owned();
+        let now = crate::serde::timestamp();
+
+        self.with(move |conn| {
+            let tx = conn.transaction()?;
+
+            if let Some(key) = dedup {
+                tx.execute(
+                    "DELETE FROM downloads WHERE created < ?",
+                    [now - TRENDING_WINDOW],
+                )?;
+                let inserted = tx.execute(
+                    "INSERT OR IGNORE INTO downloads (key, created) VALUES (?, ?)",
+                    params![format!("{id} {key}"), now],
+                )?;
+                if inserted == 0 {
+                    return Ok(());
+                }
+            }
+
+            tx.execute(
+                "UPDATE mods SET downloads = downloads + 1,
+                entry = json_set(entry, '$.downloads', downloads + 1) WHERE id = ?",
+                [&id],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+
+    async fn recent_downloads(&self, since: i64) -> StoreResult<HashMap<String, u32>> {
+        self.with(move |conn| {
+            // Keys are the mod's ID and the deduplication key, separated by a space
+            let mut stmt = conn.prepare(
+                "SELECT substr(key, 1, instr(key, ' ') - 1) AS mod_id, COUNT(*) FROM downloads
+                WHERE created >= ? GROUP BY mod_id",
+            )?;
+            let rows = stmt.query_map([since], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    async fn set_trending(&self, id: &str, trending: u32) -> StoreResult<bool> {
+        let id = id.to_owned();
+
+        self.with(move |conn| {
+            conn.execute(
+                "UPDATE mods SET trending = ?1, entry = json_set(entry, '$.trending', ?1) WHERE id = ?2",
+                params![trending, id],
+            )
+            .map(|n| n > 0)
+        })
+        .await
+    }
+
+    async fn record_audit(&self, event: &AuditEvent) -> StoreResult<()> {
+        let json = serde_json::to_string(event).expect("Failed to ser AuditEvent");
+        let (id, time) = (event.mod_id.clone(), event.time);
+
+        self.with(move |conn| {
+            conn.execute(
+                "INSERT INTO audit (mod_id, time, event) VALUES (?, ?, ?)",
+                params![id, time, json],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    async fn audit_log(&self, id: &str, skip: u64, limit: u64) -> StoreResult<Vec<AuditEvent>> {
+        let id = id.to_owned();
+        let (skip, limit) = (
+            skip.min(i64::MAX as u64) as i64,
+            limit.min(i64::MAX as u64) as i64,
+        );
+
+        self.with(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT event FROM audit WHERE mod_id = ? ORDER BY time DESC, rowid DESC LIMIT ? OFFSET ?",
+            )?;
+            let rows = stmt.query_map(params![id, limit, skip], |row| row.get::<_, String>(0))?;
+            rows.map(|json| json.and_then(|json| from_json(&json)))
+                .collect()
+        })
+        .await
+    }
+}
+
+// Selects every mod the query could list, on any page
+fn filter(select: &str, query: &ListQuery) -> (String, Vec<Value>) {
+    let mut sql = format!("{select} FROM mods WHERE 1 = 1");
+    let mut args = Vec::new();
+
+    if !query.hidden {
+        sql.push_str(" AND listed = 1");
+    }
+    if !query.broken {
+        sql.push_str(" AND json_extract(entry, '$.broken') IS NOT 1");
+    }
+    if let Some(version) = query.game {
+        sql.push_str(
+            " AND json_extract(entry, '$.game_range.min') <= ? AND json_extract(entry, '$.game_range.max') > ?",
+        );
+        args.extend([Value::Integer(version), Value::Integer(version)]);
+    }
+    for tag in query.tags {
+        sql.push_str(" AND id IN (SELECT id FROM mod_tags WHERE tag = ?)");
+        args.push(Value::Text(tag.clone()));
+    }
+
+    if let Some(search) = query.search {
+        let (score, terms) = score(search);
+        sql.push_str(&format!(" AND ({score}) >= ?"));
+        args.extend(terms);
+        args.push(Value::Integer(search::MIN_SCORE as i64));
+    }
+
+    (sql, args)
+}
+
+// An expression for how well the current row scores against a search, and its arguments
+fn score(search: &str) -> (String, Vec<Value>) {
+    let terms: Vec<Value> = search::query_terms(search)
+        .into_iter()
+        .map(Value::Text)
+        .collect();
+    let marks = vec!["?"; terms.len()].join(", ");
+    let sql = format!(
+        "SELECT coalesce(SUM(weight), 0) FROM mod_words w WHERE w.id = mods.id AND w.word IN ({marks})"
+    );
+    (sql, terms)
+}
+
+fn sort_column(sort: Sort) -> &'static str {
+    match sort {
+        Sort::New | Sort::Old => "updated",
+        Sort::MostDownloads | Sort::LeastDownloads => "downloads",
+        Sort::PublishedNew | Sort::PublishedOld => "published",
+        Sort::Trending => "trending",
+        // Relevance is ranked separately, and ties fall back to the ID like everything else
+        Sort::Name | Sort::Relevance => "id",
+    }
+}
+
+fn get(conn: &Connection, id: &str) -> rusqlite::Result<Option<ModEntry>> {
+    conn.query_row("SELECT entry FROM mods WHERE id = ?", [id], |row| {
+        row.get::<_, String>(0)
+    })
+    .optional()?
+    .map(|json| from_json(&json))
+    .transpose()
+}
+
+// Writes an entry along with the columns and search index derived from it
+fn put(conn: &Connection, entry: &ModEntry) -> rusqlite::Result<()> {
+    put_row(conn, entry)?;
+    index(conn, entry)
+}
+
+// Writes an entry along with the columns derived from it, but not its search index
+fn put_row(conn: &Connection, entry: &ModEntry) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(entry).expect("Failed to ser ModEntry");
+    let listed = !entry.unlisted && entry.deleted.is_none();
+
+    conn.execute(
+        "INSERT INTO mods (id, updated, downloads, listed, entry, published, trending)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ON CONFLICT (id) DO UPDATE SET updated = ?2, downloads = ?3, listed = ?4, entry = ?5,
+        published = ?6, trending = ?7",
+        params![
+            entry.id,
+            entry.updated,
+            entry.downloads.unwrap_or(0),
+            listed,
+            json,
+            entry.published,
+            entry.trending
+        ],
+    )?;
+    Ok(())
+}
+
+// Replaces the grams, tags and words an entry is found by
+fn index(conn: &Connection, entry: &ModEntry) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM mod_grams WHERE id = ?", [&entry.id])?;
+    for gram in entry.search.split_whitespace() {
+        conn.execute(
+            "INSERT OR IGNORE INTO mod_grams (gram, id) VALUES (?, ?)",
+            params![gram.to_lowercase(), entry.id],
+        )?;
+    }
+
+    conn.execute("DELETE FROM mod_tags WHERE id = ?", [&entry.id])?;
+    for tag in &entry.info.tags {
+        conn.execute(
+            "INSERT OR IGNORE INTO mod_tags (tag, id) VALUES (?, ?)",
+            params![tag, entry.id],
+        )?;
+    }
+
+    conn.execute("DELETE FROM mod_words WHERE id = ?", [&entry.id])?;
+    for term in &entry.terms {
+        conn.execute(
+            "INSERT OR IGNORE INTO mod_words (word, id, weight) VALUES (?, ?, ?)",
+            params![term.term, entry.id, term.weight],
+        )?;
+    }
+    Ok(())
+}
+
+fn from_json<T: rocket::serde::DeserializeOwned>(json: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
@@ -0,0 +1,478 @@
+use super::{memory::MemoryStore, sqlite::SqliteStore, *};
+use crate::{
+    prelude::Mods,
+    providers::{Provider, Providers},
+    serde::Submission,
+};
+use rocket::serde::json::{serde_json, serde_json::json, Value};
+use std::sync::Arc;
+
+// Every store runs the same tests, so they all behave the same
+macro_rules! conformance {
+    ($($test:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[rocket::async_test]
+                async fn $test() {
+                    super::$test(&super::MemoryStore::default()).await;
+                }
+            )*
+        }
+
+        mod sqlite {
+            $(
+                #[rocket::async_test]
+                async fn $test() {
+                    super::$test(&super::SqliteStore::open(":memory:")).await;
+                }
+            )*
+        }
+    };
+}
+
+conformance!(
+    lists_sorts_and_pages,
+    searches,
+    filters_by_tag,
+    filters_by_game_version,
+    upserts_newer_releases,
+    keeps_takedowns,
+    renames,
+    counts_downloads,
+    withdraws_current_release,
+);
+
+fn submission(id: &str, version: &str) -> Value {
+    let (owner, name) = id.split_once('/').unwrap();
+    json!({
+        "name": name,
+        "owner": owner,
+        "secret": "hunter2",
+        "description": "A plugin for Rain World",
+        "homepage": "",
+        "version": version,
+        "icon": format!("https://raw.githubusercontent.com/{id}/master/icon.png"),
+        "binary": format!("https://github.com/{id}/releases/download/{version}/{name}.dll")
+    })
+}
+
+async fn entry_from(submission: Value, updated: i64) -> ModEntry {
+    let submission: Submission = serde_json::from_value(submission).unwrap();
+    let mods: Mods = Arc::new(MemoryStore::default());
+    let providers = Providers::new(&Provider::defaults()).unwrap();
+
+    let mut entry = ModEntry::from_submission(submission, &mods, &providers)
+        .await
+        .unwrap();
+    entry.published = updated;
+    entry.updated = updated;
+    entry
+}
+
+async fn entry(id: &str, version: &str, updated: i64) -> ModEntry {
+    entry_from(submission(id, version), updated).await
+}
+
+fn query(sort: Sort) -> ListQuery<'static> {
+    ListQuery {
+        sort,
+        search: None,
+        tags: &[],
+        game: None,
+        skip: 0,
+        limit: 100,
+        after: None,
+        hidden: false,
+        broken: false,
+    }
+}
+
+async fn ids(store: &dyn ModStore, query: &ListQuery<'_>) -> Vec<String> {
+    let entries = store.list(query).await.unwrap();
+    entries.into_iter().map(|e| e.id).collect()
+}
+
+async fn lists_sorts_and_pages(store: &dyn ModStore) {
+    let mut one = entry("a/one", "1.0.0", 1).await;
+    one.downloads = Some(5);
+    let mut two = entry("b/two", "1.0.0", 3).await;
+    two.downloads = Some(1);
+    let mut three = entry("c/three", "1.0.0", 2).await;
+    three.downloads = Some(9);
+    let mut hidden = entry("d/four", "1.0.0", 4).await;
+    hidden.unlisted = true;
+    for e in [&one, &two, &three, &hidden] {
+        store.import(e).await.unwrap();
+    }
+
+    let new = query(Sort::New);
+    assert_eq!(ids(store, &new).await, ["b/two", "c/three", "a/one"]);
+    assert_eq!(
+        ids(store, &query(Sort::Old)).await,
+        ["a/one", "c/three", "b/two"]
+    );
+    assert_eq!(
+        ids(store, &query(Sort::MostDownloads)).await,
+        ["c/three", "a/one", "b/two"]
+    );
+    assert_eq!(
+        ids(store, &query(Sort::Name)).await,
+        ["a/one", "b/two", "c/three"]
+    );
+    assert_eq!(store.count().await.unwrap(), 3);
+    assert_eq!(store.count_matching(&new).await.unwrap(), 3);
+
+    let all = ListQuery {
+        hidden: true,
+        ..query(Sort::New)
+    };
+    assert_eq!(ids(store, &all).await[0], "d/four");
+    assert_eq!(store.count_matching(&all).await.unwrap(), 4);
+
+    // Pages either start after a cursor or skip entries, and neither changes the total
+    let first = ListQuery {
+        limit: 2,
+        ..query(Sort::New)
+    };
+    assert_eq!(ids(store, &first).await, ["b/two", "c/three"]);
+    assert_eq!(store.count_matching(&first).await.unwrap(), 3);
+
+    let after = ListQuery {
+        after: Some(Cursor {
+            key: 2,
+            id: "c/three".to_owned(),
+        }),
+        ..query(Sort::New)
+    };
+    assert_eq!(ids(store, &after).await, ["a/one"]);
+
+    let after_name = ListQuery {
+        after: Some(Cursor {
+            key: 0,
+            id: "a/one".to_owned(),
+        }),
+        ..query(Sort::Name)
+    };
+    assert_eq!(ids(store, &after_name).await, ["b/two", "c/three"]);
+
+    let skipped = ListQuery {
+        skip: 1,
+        limit: 1,
+        ..query(Sort::MostDownloads)
+    };
+    assert_eq!(ids(store, &skipped).await, ["a/one"]);
+}
+
+async fn searches(store: &dyn ModStore) {
+    let shields = entry("dual-iron/centipede-shields", "1.0.0", 1).await;
+    let mut tamer = entry("slime/lizard-tamer", "1.0.0", 2).await;
+    tamer.info.description = "Befriend every lizard".to_owned();
+    tamer.reindex();
+    store.import(&shields).await.unwrap();
+    store.import(&tamer).await.unwrap();
+
+    let typo = ListQuery {
+        search: Some("sheilds"),
+        ..query(Sort::Relevance)
+    };
+    assert_eq!(ids(store, &typo).await, ["dual-iron/centipede-shields"]);
+    assert_eq!(store.count_matching(&typo).await.unwrap(), 1);
+
+    // Changes to other fields leave entries searchable
+    assert!(store
+        .set_link_status("dual-iron/centipede-shields", 5, false)
+        .await
+        .unwrap());
+    store
+        .count_download("dual-iron/centipede-shields", None)
+        .await
+        .unwrap();
+    assert_eq!(ids(store, &typo).await, ["dual-iron/centipede-shields"]);
+
+    let befriend = ListQuery {
+        search: Some("befriend"),
+        ..query(Sort::New)
+    };
+    assert_eq!(ids(store, &befriend).await, ["slime/lizard-tamer"]);
+
+    let nothing = ListQuery {
+        search: Some("scavenger"),
+        ..query(Sort::Relevance)
+    };
+    assert!(ids(store, &nothing).await.is_empty());
+
+    let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        store.suggest(&words(&["cen"]), 10).await.unwrap(),
+        ["dual-iron/centipede-shields"]
+    );
+    assert_eq!(
+        store.suggest(&words(&["liz", "tame"]), 10).await.unwrap(),
+        ["slime/lizard-tamer"]
+    );
+    assert!(store
+        .suggest(&words(&["liz", "shi"]), 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+async fn filters_by_tag(store: &dyn ModStore) {
+    let mut one = entry("a/one", "1.0.0", 1).await;
+    one.info.tags = vec!["creature".to_owned(), "gameplay".to_owned()];
+    let mut two = entry("b/two", "1.0.0", 2).await;
+    two.info.tags = vec!["creature".to_owned()];
+    let three = entry("c/three", "1.0.0", 3).await;
+    let mut hidden = entry("d/four", "1.0.0", 4).await;
+    hidden.info.tags = vec!["creature".to_owned()];
+    hidden.deleted = Some(4);
+    for e in [&one, &two, &three, &hidden] {
+        store.import(e).await.unwrap();
+    }
+
+    let creature = ["creature".to_owned()];
+    let both = ["creature".to_owned(), "gameplay".to_owned()];
+    let tagged = |tags| ListQuery {
+        tags,
+        ..query(Sort::Name)
+    };
+    assert_eq!(ids(store, &tagged(&creature)).await, ["a/one", "b/two"]);
+    assert_eq!(ids(store, &tagged(&both)).await, ["a/one"]);
+    assert_eq!(store.count_matching(&tagged(&both)).await.unwrap(), 1);
+
+    let counts = store.tag_counts().await.unwrap();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts["creature"], 2);
+    assert_eq!(counts["gameplay"], 1);
+}
+
+async fn filters_by_game_version(store: &dyn ModStore) {
+    let mut modern = submission("a/one", "1.0.0");
+    modern["game_versions"] = json!(">=1.9, <2");
+    let mut legacy = submission("b/two", "1.0.0");
+    legacy["game_versions"] = json!("<1.9");
+    store.import(&entry_from(modern, 1).await).await.unwrap();
+    store.import(&entry_from(legacy, 2).await).await.unwrap();
+    store
+        .import(&entry("c/three", "1.0.0", 3).await)
+        .await
+        .unwrap();
+
+    let game = |version| ListQuery {
+        game: crate::compat::parse_version(version).and_then(|v| crate::compat::pack(&v)),
+        ..query(Sort::Name)
+    };
+    assert_eq!(ids(store, &game("1.9.15")).await, ["a/one"]);
+    assert_eq!(ids(store, &game("1.5")).await, ["b/two"]);
+    assert!(ids(store, &game("2.0")).await.is_empty());
+    assert_eq!(store.count_matching(&game("1.9.15")).await.unwrap(), 1);
+}
+
+async fn upserts_newer_releases(store: &dyn ModStore) {
+    let first = entry("a/one", "1.0.0", 1).await;
+    assert!(matches!(
+        store.upsert_if_newer(&first).await.unwrap(),
+        Upsert::Inserted
+    ));
+    assert!(matches!(
+        store.upsert_if_newer(&first).await.unwrap(),
+        Upsert::Outdated
+    ));
+
+    store.count_download("a/one", None).await.unwrap();
+    store.withdraw("a/one", "1.0.0").await.unwrap();
+    store.set_deleted("a/one", Some(5)).await.unwrap();
+
+    let mut second = entry("a/one", "1.1.0", 6).await;
+    second.secret = "someone else".to_owned();
+    assert!(matches!(
+        store.upsert_if_newer(&second).await.unwrap(),
+        Upsert::Updated
+    ));
+
+    // The new release replaces the old one, but the entry keeps what it's accumulated
+    let stored = store.get("a/one").await.unwrap().unwrap();
+    assert_eq!(stored.info.version, "1.1.0");
+    assert_eq!(stored.secret, "hunter2");
+    assert_eq!(stored.downloads, Some(1));
+    assert_eq!((stored.published, stored.updated), (1, 6));
+    assert_eq!(stored.deleted, None);
+    assert!(!stored.withdrawn);
+    let history: Vec<String> = stored
+        .history()
+        .into_iter()
+        .map(|r| r.info.version)
+        .collect();
+    assert_eq!(history, ["1.0.0", "1.1.0"]);
+
+    assert!(matches!(
+        store
+            .upsert_if_newer(&entry("a/one", "1.0.5", 7).await)
+            .await
+            .unwrap(),
+        Upsert::Outdated
+    ));
+}
+
+async fn keeps_takedowns(store: &dyn ModStore) {
+    store
+        .upsert_if_newer(&entry("a/one", "1.0.0", 1).await)
+        .await
+        .unwrap();
+    assert!(store.set_removed("a/one", Some(2)).await.unwrap());
+
+    let newer = entry("a/one", "1.1.0", 3).await;
+    assert!(matches!(
+        store.upsert_if_newer(&newer).await.unwrap(),
+        Upsert::Removed
+    ));
+    let stored = store.get("a/one").await.unwrap().unwrap();
+    assert_eq!(stored.info.version, "1.0.0");
+    assert_eq!((stored.removed, stored.deleted), (Some(2), Some(2)));
+    assert!(ids(store, &query(Sort::New)).await.is_empty());
+
+    // Only lifting the takedown lets new releases in
+    assert!(store.set_removed("a/one", None).await.unwrap());
+    assert!(matches!(
+        store.upsert_if_newer(&newer).await.unwrap(),
+        Upsert::Updated
+    ));
+    assert!(!store.set_removed("b/two", Some(4)).await.unwrap());
+}
+
+async fn renames(store: &dyn ModStore) {
+    store
+        .import(&entry("a/one", "1.0.0", 1).await)
+        .await
+        .unwrap();
+    store
+        .import(&entry("b/two", "1.0.0", 2).await)
+        .await
+        .unwrap();
+    store.set_icon("a/one", b"png").await.unwrap();
+
+    assert!(store.rename("a/one", "a/uno").await.unwrap());
+    assert!(store.get("a/one").await.unwrap().is_none());
+    assert_eq!(store.get("a/uno").await.unwrap().unwrap().id, "a/uno");
+    assert_eq!(
+        store.icon("a/uno").await.unwrap().as_deref(),
+        Some(&b"png"[..])
+    );
+    assert_eq!(store.icon("a/one").await.unwrap(), None);
+
+    // The entry is searched by its new name
+    assert_eq!(
+        store.suggest(&["uno".to_owned()], 10).await.unwrap(),
+        ["a/uno"]
+    );
+    assert!(store
+        .suggest(&["one".to_owned()], 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert!(matches!(
+        store.rename("a/uno", "b/two").await,
+        Err(StoreError::Conflict)
+    ));
+    assert!(!store.rename("c/three", "c/tres").await.unwrap());
+}
+
+async fn counts_downloads(store: &dyn ModStore) {
+    store
+        .import(&entry("a/one", "1.0.0", 1).await)
+        .await
+        .unwrap();
+    store
+        .import(&entry("b/two", "1.0.0", 2).await)
+        .await
+        .unwrap();
+
+    for key in ["1.2.3.4", "1.2.3.4", "5.6.7.8"] {
+        store
+            .count_download("a/one", Some(key.to_owned()))
+            .await
+            .unwrap();
+    }
+    store.count_download("a/one", None).await.unwrap();
+    store
+        .count_download("b/two", Some("1.2.3.4".to_owned()))
+        .await
+        .unwrap();
+    store.count_download("c/three", None).await.unwrap();
+
+    let downloads = |id| async move { store.get(id).await.unwrap().unwrap().downloads };
+    assert_eq!(downloads("a/one").await, Some(3));
+    assert_eq!(downloads("b/two").await, Some(1));
+
+    // Only deduplicated downloads are recorded
+    let recent = store.recent_downloads(0).await.unwrap();
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent["a/one"], 2);
+    assert_eq!(recent["b/two"], 1);
+    assert!(store
+        .recent_downloads(crate::serde::timestamp() + 60)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert!(store.set_trending("a/one", 2).await.unwrap());
+    assert_eq!(store.get("a/one").await.unwrap().unwrap().trending, 2);
+    assert!(!store.set_trending("c/three", 2).await.unwrap());
+    let trending = ids(store, &query(Sort::Trending)).await;
+    assert_eq!(trending, ["a/one", "b/two"]);
+}
+
+async fn withdraws_current_release(store: &dyn ModStore) {
+    store
+        .import(&entry("a/one", "1.0.0", 1).await)
+        .await
+        .unwrap();
+
+    assert!(!store.withdraw("a/one", "0.9.0").await.unwrap());
+    assert!(!store.get("a/one").await.unwrap().unwrap().withdrawn);
+    assert!(store.withdraw("a/one", "1.0.0").await.unwrap());
+    assert!(store.get("a/one").await.unwrap().unwrap().withdrawn);
+    assert!(!store.withdraw("b/two", "1.0.0").await.unwrap());
+}
+
+#[rocket::async_test]
+async fn migrates_between_stores() {
+    let from = MemoryStore::default();
+    let to = SqliteStore::open(":memory:");
+
+    let mut one = entry("a/one", "1.0.0", 1).await;
+    one.downloads = Some(7);
+    from.import(&one).await.unwrap();
+    from.import(&entry("b/two", "1.0.0", 2).await)
+        .await
+        .unwrap();
+    from.set_icon("a/one", b"png").await.unwrap();
+    for outcome in ["inserted", "updated"] {
+        let event = AuditEvent::new("a/one", "submit", outcome, None);
+        from.record_audit(&event).await.unwrap();
+    }
+
+    assert_eq!(migrate(&from, &to).await.unwrap(), 2);
+
+    for id in ["a/one", "b/two"] {
+        let json = |e: Option<ModEntry>| serde_json::to_value(e.unwrap()).unwrap();
+        assert_eq!(
+            json(to.get(id).await.unwrap()),
+            json(from.get(id).await.unwrap())
+        );
+    }
+    assert_eq!(
+        to.icon("a/one").await.unwrap().as_deref(),
+        Some(&b"png"[..])
+    );
+
+    let outcomes: Vec<String> = to
+        .audit_log("a/one", 0, 50)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|e| e.outcome)
+        .collect();
+    assert_eq!(outcomes, ["updated", "inserted"]);
+}
@@ -0,0 +1,55 @@
+/// Every tag a mod can have. Tags are curated so that listings can be filtered reliably.
+pub(crate) const TAGS: &[&str] = &[
+    "accessibility",
+    "arena",
+    "audio",
+    "cheats",
+    "cosmetic",
+    "creature",
+    "gameplay",
+    "item",
+    "library",
+    "multiplayer",
+    "quality-of-life",
+    "region",
+    "story",
+    "tool",
+    "translation",
+    "ui",
+];
+
+pub(crate) const MAX_TAGS: usize = 5;
+
+/// Lowercases and trims each tag, then sorts them and removes duplicates.
+pub(crate) fn normalise(tags: &mut Vec<String>) {
+    for tag in tags.iter_mut() {
+        *tag = tag.trim().to_lowercase();
+    }
+    tags.sort();
+    tags.dedup();
+}
+
+pub(crate) fn errors(tags: &[String]) -> Option<&'static str> {
+    if tags.len() > MAX_TAGS {
+        Some("Mods can have at most 5 tags.")
+    } else if tags.iter().any(|t| !TAGS.contains(&t.as_str())) {
+        Some("Each tag must be one of the tags listed by `GET /tags`.")
+    } else {
+        None
+    }
+}
+
+/// Parses a comma-separated list of tags from a query string. Fails on unknown tags.
+pub(crate) fn parse(list: &str) -> Option<Vec<String>> {
+    let mut tags: Vec<String> = list
+        .split(',')
+        .filter(|t| !t.trim().is_empty())
+        .map(str::to_owned)
+        .collect();
+    normalise(&mut tags);
+
+    match tags.iter().all(|t| TAGS.contains(&t.as_str())) {
+        true => Some(tags),
+        false => None,
+    }
+}
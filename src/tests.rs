@@ -0,0 +1,519 @@
+use crate::{
+    config::{Config, FetchMode, RateLimit},
+    guards::Syncs,
+    store::memory::MemoryStore,
+};
+use hmac::{Hmac, Mac};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::asynchronous::Client,
+    serde::json::{serde_json, serde_json::json, Value},
+};
+use sha2::Sha256;
+
+const ADMIN_TOKEN: &str = "admin-token-for-tests";
+
+fn config() -> Config {
+    Config {
+        db_url: "memory://".to_owned(),
+        secret_key: "secret key for tests".to_owned(),
+        admin_token: Some(ADMIN_TOKEN.to_owned()),
+        fetcher: FetchMode::Stub,
+        ..Default::default()
+    }
+}
+
+async fn client() -> Client {
+    client_with(config()).await
+}
+
+async fn client_with(config: Config) -> Client {
+    let keys = crate::secrets::HookKeys::new(&config.secret_key);
+    let app = crate::app(std::sync::Arc::new(MemoryStore::default()), keys, config);
+
+    Client::tracked(app).await.expect("valid rocket instance")
+}
+
+fn submission(version: &str, secret: &str) -> Value {
+    json!({
+        "name": "centipede-shields",
+        "owner": "Dual-Iron",
+        "secret": secret,
+        "description": "A plugin for Rain World",
+        "homepage": "",
+        "version": version,
+        "icon": "https://raw.githubusercontent.com/Dual-Iron/centipede-shields/master/icon.png",
+        "binary": format!("https://github.com/Dual-Iron/centipede-shields/releases/download/{version}/CentiShields.dll")
+    })
+}
+
+async fn send(c: &Client, method: &str, uri: &str, body: Option<Value>) -> (Status, String) {
+    let req = match method {
+        "POST" => c.post(uri.to_owned()),
+        "DELETE" => c.delete(uri.to_owned()),
+        _ => c.get(uri.to_owned()),
+    };
+    let req = match body {
+        Some(body) => req.header(ContentType::JSON).body(body.to_string()),
+        None => req,
+    };
+    let res = req.dispatch().await;
+    (res.status(), res.into_string().await.unwrap_or_default())
+}
+
+async fn submit(c: &Client, version: &str, secret: &str) -> (Status, String) {
+    send(c, "POST", "/mods", Some(submission(version, secret))).await
+}
+
+async fn get_mod(c: &Client) -> (Status, Value) {
+    let (status, body) = send(c, "GET", "/mods/Dual-Iron/centipede-shields", None).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+// A release delivery for `repo`, signed with `secret`, which is also put in the query if `query`
+async fn deliver(
+    c: &Client,
+    repo: &str,
+    action: &str,
+    tag: &str,
+    secret: &str,
+    query: bool,
+) -> (Status, String) {
+    let body = json!({
+        "action": action,
+        "repository": { "full_name": repo, "description": "A plugin for Rain World", "homepage": null },
+        "release": {
+            "tag_name": tag,
+            "draft": false,
+            "prerelease": tag.contains('-'),
+            "body": null,
+            "assets": [{
+                "name": "CentiShields.dll",
+                "browser_download_url": format!("https://github.com/{repo}/releases/download/{tag}/CentiShields.dll")
+            }]
+        }
+    })
+    .to_string();
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+    let uri = match query {
+        true => format!("/github?secret={secret}"),
+        false => "/github".to_owned(),
+    };
+    let res = c
+        .post(uri)
+        .header(ContentType::JSON)
+        .header(Header::new("X-GitHub-Event", "release"))
+        .header(Header::new("X-Hub-Signature-256", signature))
+        .body(body)
+        .dispatch()
+        .await;
+    (res.status(), res.into_string().await.unwrap_or_default())
+}
+
+// The mod's audit log, once it has at least `len` events. Deliveries are synced in the background.
+async fn audited(c: &Client, len: usize) -> Value {
+    for _ in 0..1000 {
+        let res = c
+            .get("/mods/Dual-Iron/centipede-shields/audit")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {ADMIN_TOKEN}"),
+            ))
+            .dispatch()
+            .await;
+        let log: Value = serde_json::from_str(&res.into_string().await.unwrap_or_default())
+            .unwrap_or(Value::Null);
+        if log.as_array().is_some_and(|events| events.len() >= len) {
+            return log;
+        }
+        rocket::tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("the audit log never had {len} events");
+}
+
+async fn count(c: &Client) -> String {
+    send(c, "GET", "/mods/count", None).await.1
+}
+
+#[rocket::async_test]
+async fn submit_verify_outdated() {
+    let c = client().await;
+
+    assert_eq!(
+        submit(&c, "1.0.0", "hunter2").await,
+        (Status::Ok, "Successfully inserted mod.".to_owned())
+    );
+    let (status, entry) = get_mod(&c).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(entry["version"], "1.0.0");
+    assert!(entry["sha256"].is_string());
+    assert_eq!(
+        entry["icon"],
+        "https://rdb.dual-iron.xyz/mods/Dual-Iron/centipede-shields/icon.png"
+    );
+    assert_eq!(
+        entry["icon_source"],
+        "https://raw.githubusercontent.com/Dual-Iron/centipede-shields/master/icon.png"
+    );
+
+    assert_eq!(
+        submit(&c, "1.0.0", "hunter2").await,
+        (Status::BadRequest, "The version is outdated.".to_owned())
+    );
+    assert_eq!(
+        submit(&c, "1.1.0", "wrong").await,
+        (Status::BadRequest, "The secret is incorrect.".to_owned())
+    );
+    assert_eq!(
+        submit(&c, "v1.1.0", "hunter2").await,
+        (Status::Ok, "Successfully updated mod.".to_owned())
+    );
+
+    let (_, versions) = send(
+        &c,
+        "GET",
+        "/mods/Dual-Iron/centipede-shields/versions",
+        None,
+    )
+    .await;
+    let versions: Value = serde_json::from_str(&versions).unwrap();
+    assert_eq!(versions[0]["version"], "1.1.0");
+    assert_eq!(versions[1]["version"], "1.0.0");
+}
+
+#[rocket::async_test]
+async fn rejects_invalid_submissions() {
+    let c = client().await;
+
+    let mut bad_version = submission("1.0.0", "hunter2");
+    bad_version["version"] = json!("one");
+    let (status, _) = send(&c, "POST", "/mods", Some(bad_version)).await;
+    assert_eq!(status, Status::BadRequest);
+
+    let mut bad_binary = submission("1.0.0", "hunter2");
+    bad_binary["binary"] = json!("https://example.com/CentiShields.dll");
+    let (status, _) = send(&c, "POST", "/mods", Some(bad_binary)).await;
+    assert_eq!(status, Status::BadRequest);
+
+    assert_eq!(get_mod(&c).await.0, Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn delete_and_unlist() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+    let uri = "/mods/Dual-Iron/centipede-shields";
+
+    let unlist = json!({ "secret": "hunter2", "unlisted": true });
+    let (status, _) = send(&c, "POST", &format!("{uri}/visibility"), Some(unlist)).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(count(&c).await, "0");
+    assert_eq!(get_mod(&c).await.0, Status::Ok);
+
+    let wrong = json!({ "secret": "wrong" });
+    assert_eq!(
+        send(&c, "DELETE", uri, Some(wrong)).await.0,
+        Status::BadRequest
+    );
+    assert_eq!(get_mod(&c).await.0, Status::Ok);
+
+    let right = json!({ "secret": "hunter2" });
+    assert_eq!(send(&c, "DELETE", uri, Some(right)).await.0, Status::Ok);
+    assert_eq!(get_mod(&c).await.0, Status::NotFound);
+
+    // A new release from the owner restores it
+    assert_eq!(submit(&c, "1.1.0", "hunter2").await.0, Status::Ok);
+    assert_eq!(get_mod(&c).await.0, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn admin_takedown_sticks() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+    let auth = Header::new("Authorization", format!("Bearer {ADMIN_TOKEN}"));
+
+    let res = c
+        .delete("/admin/mods/Dual-Iron/centipede-shields")
+        .header(auth.clone())
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(get_mod(&c).await.0, Status::NotFound);
+
+    assert_eq!(submit(&c, "1.1.0", "hunter2").await.0, Status::Forbidden);
+    assert_eq!(get_mod(&c).await.0, Status::NotFound);
+
+    let res = c
+        .post("/admin/mods/Dual-Iron/centipede-shields/restore")
+        .header(auth)
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(get_mod(&c).await.1["version"], "1.0.0");
+    assert_eq!(submit(&c, "1.1.0", "hunter2").await.0, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn rotate_secret() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+    let uri = "/mods/Dual-Iron/centipede-shields/secret";
+
+    let wrong = json!({ "secret": "wrong", "new_secret": "hunter3" });
+    assert_eq!(
+        send(&c, "POST", uri, Some(wrong)).await.0,
+        Status::BadRequest
+    );
+
+    let right = json!({ "secret": "hunter2", "new_secret": "hunter3" });
+    assert_eq!(send(&c, "POST", uri, Some(right)).await.0, Status::Ok);
+
+    assert_eq!(
+        submit(&c, "1.1.0", "hunter2").await,
+        (Status::BadRequest, "The secret is incorrect.".to_owned())
+    );
+    assert_eq!(submit(&c, "1.1.0", "hunter3").await.0, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn hook_limits_per_repository() {
+    let c = client_with(Config {
+        rate_limit: RateLimit {
+            requests: 2,
+            window: 60,
+        },
+        ..config()
+    })
+    .await;
+    let repo = "Dual-Iron/centipede-shields";
+    submit(&c, "1.0.0", "hunter2").await;
+
+    assert_eq!(
+        deliver(&c, repo, "released", "1.1.0", "hunter2", false)
+            .await
+            .0,
+        Status::Ok
+    );
+    assert_eq!(
+        deliver(&c, repo, "released", "1.2.0", "hunter2", false)
+            .await
+            .0,
+        Status::Ok
+    );
+    assert_eq!(
+        deliver(&c, repo, "released", "1.3.0", "hunter2", false)
+            .await
+            .0,
+        Status::TooManyRequests
+    );
+
+    // Other repositories aren't held up by a busy one
+    let mut other = submission("1.0.0", "hunter2");
+    other["name"] = json!("other-mod");
+    send(&c, "POST", "/mods", Some(other)).await;
+    assert_eq!(
+        deliver(
+            &c,
+            "Dual-Iron/other-mod",
+            "released",
+            "1.1.0",
+            "hunter2",
+            false
+        )
+        .await
+        .0,
+        Status::Ok
+    );
+}
+
+#[rocket::async_test]
+async fn hook_syncs_in_background() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+    let repo = "Dual-Iron/centipede-shields";
+
+    let (status, body) = deliver(&c, repo, "released", "1.1.0", "hunter2", false).await;
+    assert_eq!(status, Status::Ok);
+    assert!(body.starts_with("Accepted."));
+    assert_eq!(audited(&c, 2).await[0]["outcome"], "updated");
+    assert_eq!(get_mod(&c).await.1["version"], "1.1.0");
+
+    // Only fetching files is put off, so everything else is still reported to GitHub
+    let (status, _) = deliver(&c, repo, "released", "one", "hunter2", false).await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(audited(&c, 3).await[0]["outcome"], "invalid");
+    assert_eq!(
+        deliver(&c, repo, "released", "1.0.5", "hunter2", false).await,
+        (Status::BadRequest, "The version is outdated.".to_owned())
+    );
+    assert_eq!(
+        deliver(&c, repo, "prereleased", "1.0.5-beta", "hunter2", false).await,
+        (Status::BadRequest, "The version is outdated.".to_owned())
+    );
+    assert_eq!(get_mod(&c).await.1["version"], "1.1.0");
+}
+
+#[rocket::async_test]
+async fn hook_refuses_releases_while_busy() {
+    let c = client_with(Config {
+        max_syncs: 1,
+        ..config()
+    })
+    .await;
+    submit(&c, "1.0.0", "hunter2").await;
+    let repo = "Dual-Iron/centipede-shields";
+
+    let permit = c.rocket().state::<Syncs>().unwrap().reserve();
+    let (status, _) = deliver(&c, repo, "released", "1.1.0", "hunter2", false).await;
+    assert_eq!(status, Status::ServiceUnavailable);
+    assert_eq!(get_mod(&c).await.1["version"], "1.0.0");
+
+    drop(permit);
+    let (status, _) = deliver(&c, repo, "released", "1.1.0", "hunter2", false).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(audited(&c, 2).await[0]["outcome"], "updated");
+}
+
+#[rocket::async_test]
+async fn hook_needs_the_mods_secret() {
+    let c = client().await;
+    let repo = "Dual-Iron/centipede-shields";
+
+    // New mods are submitted through `POST /mods`
+    let (status, _) = deliver(&c, repo, "released", "1.0.0", "hunter2", false).await;
+    assert_eq!(status, Status::NotFound);
+    submit(&c, "1.0.0", "hunter2").await;
+
+    // Signed with a secret of the sender's choosing, with or without it in the query
+    assert_eq!(
+        deliver(&c, repo, "deleted", "1.0.0", "forged", false).await,
+        (
+            Status::Unauthorized,
+            "The signature is incorrect.".to_owned()
+        )
+    );
+    let (status, _) = deliver(&c, repo, "deleted", "1.0.0", "forged", true).await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(get_mod(&c).await.1["withdrawn"], false);
+    assert_eq!(audited(&c, 3).await[0]["outcome"], "unauthorized");
+
+    // Even the right secret doesn't belong in the URL
+    let (status, _) = deliver(&c, repo, "deleted", "1.0.0", "hunter2", true).await;
+    assert_eq!(status, Status::BadRequest);
+
+    let (status, _) = deliver(&c, repo, "deleted", "1.0.0", "hunter2", false).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(get_mod(&c).await.1["withdrawn"], true);
+}
+
+#[rocket::async_test]
+async fn hook_allows_query_secrets_if_configured() {
+    let c = client_with(Config {
+        allow_query_secret: true,
+        ..config()
+    })
+    .await;
+    submit(&c, "1.0.0", "hunter2").await;
+    let unsigned = |secret: &str| {
+        c.post(format!("/github?secret={secret}"))
+            .header(ContentType::JSON)
+            .header(Header::new("X-GitHub-Event", "release"))
+            .body(
+                json!({
+                    "action": "deleted",
+                    "repository": { "full_name": "Dual-Iron/centipede-shields", "description": null, "homepage": null },
+                    "release": { "tag_name": "1.0.0", "draft": false, "prerelease": false, "body": null, "assets": [] }
+                })
+                .to_string(),
+            )
+    };
+
+    assert_eq!(
+        unsigned("forged").dispatch().await.status(),
+        Status::Unauthorized
+    );
+    assert_eq!(get_mod(&c).await.1["withdrawn"], false);
+    assert_eq!(unsigned("hunter2").dispatch().await.status(), Status::Ok);
+    assert_eq!(get_mod(&c).await.1["withdrawn"], true);
+}
+
+#[rocket::async_test]
+async fn hook_ignores_edits_to_synced_releases() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+    let repo = "Dual-Iron/centipede-shields";
+
+    assert_eq!(
+        deliver(&c, repo, "edited", "v1.0.0", "hunter2", false).await,
+        (
+            Status::Ok,
+            "Ignored. This release is already synced to rdb.".to_owned()
+        )
+    );
+
+    let (status, body) = deliver(&c, repo, "released", "1.1.0-beta", "hunter2", false).await;
+    assert_eq!(status, Status::Ok);
+    assert!(body.starts_with("Accepted."));
+    assert_eq!(audited(&c, 2).await[0]["outcome"], "prerelease");
+    assert_eq!(
+        deliver(&c, repo, "edited", "1.1.0-beta", "hunter2", false).await,
+        (
+            Status::Ok,
+            "Ignored. This release is already synced to rdb.".to_owned()
+        )
+    );
+
+    // Edits to releases that aren't synced yet still submit them
+    let (_, body) = deliver(&c, repo, "edited", "1.1.0", "hunter2", false).await;
+    assert!(body.starts_with("Accepted."));
+    assert_eq!(audited(&c, 3).await[0]["outcome"], "updated");
+}
+
+#[rocket::async_test]
+async fn admin_pages_past_the_end() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+
+    let res = c
+        .get(format!("/admin/mods?page={}", u64::MAX))
+        .header(Header::new(
+            "Authorization",
+            format!("Bearer {ADMIN_TOKEN}"),
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Ok);
+}
+
+#[rocket::async_test]
+async fn audit_pages_past_the_end() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+
+    let res = c
+        .get(format!(
+            "/mods/Dual-Iron/centipede-shields/audit?page={}",
+            u64::MAX
+        ))
+        .header(Header::new(
+            "Authorization",
+            format!("Bearer {ADMIN_TOKEN}"),
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(res.into_string().await.unwrap(), "[]");
+}
+
+#[rocket::async_test]
+async fn pages_past_the_end() {
+    let c = client().await;
+    submit(&c, "1.0.0", "hunter2").await;
+
+    let (status, body) = send(&c, "GET", &format!("/mods?page={}", u64::MAX), None).await;
+    assert_eq!((status, body.as_str()), (Status::Ok, "[]"));
+}
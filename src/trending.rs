@@ -0,0 +1,36 @@
+use crate::{prelude::*, store::TRENDING_WINDOW};
+use rocket::tokio::time::{sleep, Duration};
+
+// How often trending counts are recomputed
+const PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Keeps each mod's trending count in step with its recent downloads, forever.
+pub(crate) async fn run(mods: Mods) {
+    loop {
+        update(&mods).await;
+        sleep(PERIOD).await;
+    }
+}
+
+async fn update(mods: &Mods) {
+    let (entries, recent) = match (
+        mods.all().await,
+        mods.recent_downloads(timestamp() - TRENDING_WINDOW).await,
+    ) {
+        (Ok(entries), Ok(recent)) => (entries, recent),
+        (Err(e), _) | (_, Err(e)) => {
+            dbg!(e);
+            return;
+        }
+    };
+
+    // Only entries whose count changed are written
+    for entry in entries {
+        let trending = recent.get(&entry.id).copied().unwrap_or(0);
+        if trending != entry.trending {
+            if let Err(e) = mods.set_trending(&entry.id, trending).await {
+                dbg!(e);
+            }
+        }
+    }
+}